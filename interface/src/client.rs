@@ -0,0 +1,149 @@
+use std::collections::VecDeque;
+use std::io::{Error, ErrorKind, Result};
+use std::net::SocketAddr;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use crate::protocol::{Command, Response};
+
+/// Client for the 4-byte protocol of the elevator server, see Command and Response
+///
+/// Queries can be sent in batches with Client::query_all(), which writes all of
+/// them before reading any response, so a whole batch costs a single round trip.
+#[derive(Debug)]
+pub struct Client {
+    stream: TcpStream,
+}
+
+impl Client {
+    pub async fn connect(addr: SocketAddr) -> Result<Client> {
+        let stream = TcpStream::connect(addr).await?;
+        stream.set_nodelay(true)?;
+        Ok(Client { stream })
+    }
+
+    /// Sends a command that is not a query, which has no response
+    pub async fn send(&mut self, command: Command) -> Result<()> {
+        self.stream.write_all(&command.encode()).await
+    }
+
+    pub async fn query(&mut self, query: Command) -> Result<Response> {
+        let responses = self.query_all(&[query]).await?;
+        Ok(responses[0])
+    }
+
+    /// Sends every query at once, and returns the responses in the same order
+    ///
+    /// Responses are matched to the queries by opcode, in the order the queries
+    /// with that opcode were sent.
+    pub async fn query_all(&mut self, queries: &[Command]) -> Result<Vec<Response>> {
+        if let Some(command) = queries.iter().find(|command| !command.is_query()) {
+            let msg = format!("{command:?} is not a query");
+            return Err(Error::new(ErrorKind::InvalidInput, msg));
+        }
+
+        let messages: Vec<[u8; 4]> = queries.iter().map(Command::encode).collect();
+        self.stream.write_all(&messages.concat()).await?;
+
+        let mut pending: VecDeque<usize> = (0..queries.len()).collect();
+        let mut responses = vec![None; queries.len()];
+        while !pending.is_empty() {
+            let mut message = [0; 4];
+            self.stream.read_exact(&mut message).await?;
+            let response = Response::decode(message)?;
+
+            let position = pending
+                .iter()
+                .position(|&i| queries[i].opcode() == response.opcode())
+                .ok_or_else(|| {
+                    let msg = format!("got {response:?}, which matches no query");
+                    Error::new(ErrorKind::InvalidData, msg)
+                })?;
+            let index = pending.remove(position).unwrap();
+            responses[index] = Some(response);
+        }
+
+        Ok(responses.into_iter().flatten().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::net::TcpListener;
+
+    use super::*;
+    use crate::types::{Button, Direction};
+
+    async fn connect() -> (Client, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let client = Client::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+        let (server, _) = listener.accept().await.unwrap();
+        (client, server)
+    }
+
+    #[tokio::test]
+    async fn queries_are_pipelined() {
+        let (mut client, mut server) = connect().await;
+
+        // The server reads every query before answering, which would never
+        // finish if the client waited for each response
+        let server = tokio::spawn(async move {
+            let mut queries = [0; 12];
+            server.read_exact(&mut queries).await.unwrap();
+            assert_eq!(queries, [6, 0, 1, 0, 6, 0, 2, 0, 7, 0, 0, 0]);
+            server.write_all(&[6, 1, 0, 0, 6, 0, 0, 0]).await.unwrap();
+            server.write_all(&[7, 1, 3, 0]).await.unwrap();
+        });
+
+        let button = Button::Hall(Direction::Up);
+        let queries = [
+            Command::OrderButton { button, floor: 1 },
+            Command::OrderButton { button, floor: 2 },
+            Command::Floor,
+        ];
+        let responses = client.query_all(&queries).await.unwrap();
+        let expected = [
+            Response::OrderButton(true),
+            Response::OrderButton(false),
+            Response::Floor(Some(3)),
+        ];
+        assert_eq!(responses, expected);
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn responses_are_matched_by_opcode() {
+        let (mut client, mut server) = connect().await;
+
+        let server = tokio::spawn(async move {
+            let mut queries = [0; 8];
+            server.read_exact(&mut queries).await.unwrap();
+
+            // Out of order, and split over several writes
+            server.write_all(&[9, 1]).await.unwrap();
+            server.flush().await.unwrap();
+            tokio::task::yield_now().await;
+            server.write_all(&[0, 0, 8, 0, 0, 0]).await.unwrap();
+            server.write_all(&[6, 0, 0, 0]).await.unwrap();
+            server
+        });
+
+        let queries = [Command::StopButton, Command::Obstruction];
+        let responses = client.query_all(&queries).await.unwrap();
+        let expected = [Response::StopButton(false), Response::Obstruction(true)];
+        assert_eq!(responses, expected);
+
+        // A response to something that was never asked
+        let _server = server.await.unwrap();
+        let error = client.query(Command::Floor).await.unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+
+        let error = client
+            .query(Command::DoorOpenLight(true))
+            .await
+            .unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidInput);
+    }
+}
@@ -0,0 +1,75 @@
+use std::future::Future;
+use std::io::Result;
+
+use crate::types::{Button, Direction, Floor};
+
+mod edges;
+mod mock;
+mod shared;
+mod tcp;
+
+pub use self::edges::{Edge, Edges};
+pub use self::mock::MockDriver;
+pub use self::shared::SharedDriver;
+pub use self::tcp::ElevatorDriver;
+
+/// Inputs and outputs of a single elevator
+///
+/// Covers every command of the 4-byte elevator protocol, so the state machine
+/// can run against the simulator (ElevatorDriver) or in memory (MockDriver).
+pub trait HardwareDriver: Send {
+    /// Whether the hardware can currently be reached
+    fn is_connected(&self) -> bool {
+        true
+    }
+
+    fn motor_direction(&mut self, direction: Direction) -> impl Future<Output = Result<()>> + Send;
+
+    /// Stops the motor
+    fn stop(&mut self) -> impl Future<Output = Result<()>> + Send;
+
+    fn order_button_light(
+        &mut self,
+        button: Button,
+        floor: Floor,
+        on: bool,
+    ) -> impl Future<Output = Result<()>> + Send;
+
+    fn floor_indicator(&mut self, floor: Floor) -> impl Future<Output = Result<()>> + Send;
+
+    fn door_open_light(&mut self, on: bool) -> impl Future<Output = Result<()>> + Send;
+
+    fn stop_button_light(&mut self, on: bool) -> impl Future<Output = Result<()>> + Send;
+
+    /// Whether the button is currently held down
+    fn order_button(
+        &mut self,
+        button: Button,
+        floor: Floor,
+    ) -> impl Future<Output = Result<bool>> + Send;
+
+    /// Whether each of the buttons is currently held down
+    ///
+    /// Drivers that talk to the hardware over a connection should read all of
+    /// them at once, instead of one by one as done here.
+    fn order_buttons(
+        &mut self,
+        buttons: &[(Button, Floor)],
+    ) -> impl Future<Output = Result<Vec<bool>>> + Send {
+        async move {
+            let mut pressed = Vec::with_capacity(buttons.len());
+            for &(button, floor) in buttons {
+                pressed.push(self.order_button(button, floor).await?);
+            }
+            Ok(pressed)
+        }
+    }
+
+    /// The floor the car is at, or None if it is between floors
+    fn floor(&mut self) -> impl Future<Output = Result<Option<Floor>>> + Send;
+
+    /// Whether the stop button is currently held down
+    fn stop_button(&mut self) -> impl Future<Output = Result<bool>> + Send;
+
+    fn obstruction_switch(&mut self) -> impl Future<Output = Result<bool>> + Send;
+}
@@ -0,0 +1,96 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Change of a button, as found by Edges::update()
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edge {
+    Pressed,
+    Released,
+}
+
+/// Turns the levels read from buttons into press and release edges
+///
+/// The driver only tells whether a button is held down, so a button that is held
+/// is seen on every poll. This keeps the last level of each button, identified by
+/// `K`, and only reports changes. A new level has to be read for at least `debounce`
+/// before it counts, so contact bounce does not show up as several presses.
+/// A debounce of zero reports every change right away.
+#[derive(Debug)]
+pub struct Edges<K> {
+    debounce: Duration,
+    levels: HashMap<K, Level>,
+}
+
+#[derive(Debug, Default)]
+struct Level {
+    pressed: bool,
+    /// A different level that has been read since the given time
+    changing_since: Option<Instant>,
+}
+
+impl<K: Hash + Eq> Edges<K> {
+    pub fn new(debounce: Duration) -> Self {
+        Edges {
+            debounce,
+            levels: HashMap::new(),
+        }
+    }
+
+    /// Registers the level read from a button, every button starts out released
+    pub fn update(&mut self, key: K, pressed: bool) -> Option<Edge> {
+        let level = self.levels.entry(key).or_default();
+        if pressed == level.pressed {
+            level.changing_since = None;
+            return None;
+        }
+
+        let since = *level.changing_since.get_or_insert_with(Instant::now);
+        if since.elapsed() < self.debounce {
+            return None;
+        }
+
+        level.pressed = pressed;
+        level.changing_since = None;
+        Some(if pressed {
+            Edge::Pressed
+        } else {
+            Edge::Released
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::time::advance;
+
+    use super::*;
+
+    #[test]
+    fn reports_changes_only() {
+        let mut edges = Edges::new(Duration::ZERO);
+        assert_eq!(edges.update(1, false), None);
+        assert_eq!(edges.update(1, true), Some(Edge::Pressed));
+        assert_eq!(edges.update(1, true), None);
+        assert_eq!(edges.update(2, true), Some(Edge::Pressed));
+        assert_eq!(edges.update(1, false), Some(Edge::Released));
+        assert_eq!(edges.update(1, false), None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn ignores_bounces() {
+        let mut edges = Edges::new(Duration::from_millis(20));
+        assert_eq!(edges.update("stop", true), None);
+        advance(Duration::from_millis(10)).await;
+        assert_eq!(edges.update("stop", false), None);
+        advance(Duration::from_millis(30)).await;
+        assert_eq!(edges.update("stop", true), None);
+
+        advance(Duration::from_millis(10)).await;
+        assert_eq!(edges.update("stop", true), None);
+        advance(Duration::from_millis(10)).await;
+        assert_eq!(edges.update("stop", true), Some(Edge::Pressed));
+        assert_eq!(edges.update("stop", true), None);
+    }
+}
@@ -0,0 +1,114 @@
+use std::collections::{HashMap, HashSet};
+use std::io::{Error, ErrorKind, Result};
+
+use crate::types::{Button, Direction, Floor};
+
+use super::HardwareDriver;
+
+/// In-memory elevator for tests
+///
+/// Inputs are set directly on the fields, and the outputs can be read back
+/// the same way. Nothing moves by itself: the test decides when the car
+/// reaches a floor by setting `floor`.
+#[derive(Debug, Default, Clone)]
+pub struct MockDriver {
+    // Inputs
+    pub floor: Option<Floor>,
+    pub pressed: HashSet<(Button, Floor)>,
+    pub stop_button: bool,
+    pub obstruction: bool,
+    /// Every call fails with ErrorKind::NotConnected while this is set
+    pub disconnected: bool,
+
+    // Outputs
+    pub motor: Option<Direction>,
+    pub order_lights: HashMap<(Button, Floor), bool>,
+    pub floor_indicator: Option<Floor>,
+    pub door_open_light: bool,
+    pub stop_button_light: bool,
+}
+
+impl MockDriver {
+    pub fn at_floor(floor: Floor) -> Self {
+        MockDriver {
+            floor: Some(floor),
+            ..MockDriver::default()
+        }
+    }
+
+    pub fn is_lit(&self, button: Button, floor: Floor) -> bool {
+        self.order_lights.get(&(button, floor)) == Some(&true)
+    }
+
+    fn check(&self) -> Result<()> {
+        if self.disconnected {
+            return Err(Error::new(
+                ErrorKind::NotConnected,
+                "mock driver is disconnected",
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl HardwareDriver for MockDriver {
+    fn is_connected(&self) -> bool {
+        !self.disconnected
+    }
+
+    async fn motor_direction(&mut self, direction: Direction) -> Result<()> {
+        self.check()?;
+        self.motor = Some(direction);
+        Ok(())
+    }
+
+    async fn stop(&mut self) -> Result<()> {
+        self.check()?;
+        self.motor = None;
+        Ok(())
+    }
+
+    async fn order_button_light(&mut self, button: Button, floor: Floor, on: bool) -> Result<()> {
+        self.check()?;
+        self.order_lights.insert((button, floor), on);
+        Ok(())
+    }
+
+    async fn floor_indicator(&mut self, floor: Floor) -> Result<()> {
+        self.check()?;
+        self.floor_indicator = Some(floor);
+        Ok(())
+    }
+
+    async fn door_open_light(&mut self, on: bool) -> Result<()> {
+        self.check()?;
+        self.door_open_light = on;
+        Ok(())
+    }
+
+    async fn stop_button_light(&mut self, on: bool) -> Result<()> {
+        self.check()?;
+        self.stop_button_light = on;
+        Ok(())
+    }
+
+    async fn order_button(&mut self, button: Button, floor: Floor) -> Result<bool> {
+        self.check()?;
+        Ok(self.pressed.contains(&(button, floor)))
+    }
+
+    async fn floor(&mut self) -> Result<Option<Floor>> {
+        self.check()?;
+        Ok(self.floor)
+    }
+
+    async fn stop_button(&mut self) -> Result<bool> {
+        self.check()?;
+        Ok(self.stop_button)
+    }
+
+    async fn obstruction_switch(&mut self) -> Result<bool> {
+        self.check()?;
+        Ok(self.obstruction)
+    }
+}
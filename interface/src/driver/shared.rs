@@ -0,0 +1,150 @@
+use std::io::Result;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::sync::{Mutex, MutexGuard};
+
+use crate::types::{Button, Direction, Floor};
+
+use super::HardwareDriver;
+
+/// A driver that can be used by several tasks at once, e.g. one polling the
+/// inputs while another sets the outputs
+///
+/// Clones share the same driver. Every call locks it for the duration of the call,
+/// so commands from different tasks are never mixed up on the connection.
+#[derive(Debug)]
+pub struct SharedDriver<D> {
+    driver: Arc<Mutex<D>>,
+    /// Copy of driver.is_connected(), as of the last call
+    connected: Arc<AtomicBool>,
+}
+
+impl<D: HardwareDriver> SharedDriver<D> {
+    pub fn new(driver: D) -> Self {
+        SharedDriver {
+            connected: Arc::new(AtomicBool::new(driver.is_connected())),
+            driver: Arc::new(Mutex::new(driver)),
+        }
+    }
+
+    /// Direct access to the driver, waiting for any call in progress to finish
+    pub async fn lock(&self) -> MutexGuard<'_, D> {
+        self.driver.lock().await
+    }
+
+    fn update(&self, driver: &D) {
+        self.connected
+            .store(driver.is_connected(), Ordering::Relaxed);
+    }
+}
+
+impl<D> Clone for SharedDriver<D> {
+    fn clone(&self) -> Self {
+        SharedDriver {
+            driver: self.driver.clone(),
+            connected: self.connected.clone(),
+        }
+    }
+}
+
+impl<D: HardwareDriver> HardwareDriver for SharedDriver<D> {
+    fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::Relaxed)
+    }
+
+    async fn motor_direction(&mut self, direction: Direction) -> Result<()> {
+        let mut driver = self.driver.lock().await;
+        let result = driver.motor_direction(direction).await;
+        self.update(&driver);
+        result
+    }
+
+    async fn stop(&mut self) -> Result<()> {
+        let mut driver = self.driver.lock().await;
+        let result = driver.stop().await;
+        self.update(&driver);
+        result
+    }
+
+    async fn order_button_light(&mut self, button: Button, floor: Floor, on: bool) -> Result<()> {
+        let mut driver = self.driver.lock().await;
+        let result = driver.order_button_light(button, floor, on).await;
+        self.update(&driver);
+        result
+    }
+
+    async fn floor_indicator(&mut self, floor: Floor) -> Result<()> {
+        let mut driver = self.driver.lock().await;
+        let result = driver.floor_indicator(floor).await;
+        self.update(&driver);
+        result
+    }
+
+    async fn door_open_light(&mut self, on: bool) -> Result<()> {
+        let mut driver = self.driver.lock().await;
+        let result = driver.door_open_light(on).await;
+        self.update(&driver);
+        result
+    }
+
+    async fn stop_button_light(&mut self, on: bool) -> Result<()> {
+        let mut driver = self.driver.lock().await;
+        let result = driver.stop_button_light(on).await;
+        self.update(&driver);
+        result
+    }
+
+    async fn order_button(&mut self, button: Button, floor: Floor) -> Result<bool> {
+        let mut driver = self.driver.lock().await;
+        let result = driver.order_button(button, floor).await;
+        self.update(&driver);
+        result
+    }
+
+    async fn order_buttons(&mut self, buttons: &[(Button, Floor)]) -> Result<Vec<bool>> {
+        let mut driver = self.driver.lock().await;
+        let result = driver.order_buttons(buttons).await;
+        self.update(&driver);
+        result
+    }
+
+    async fn floor(&mut self) -> Result<Option<Floor>> {
+        let mut driver = self.driver.lock().await;
+        let result = driver.floor().await;
+        self.update(&driver);
+        result
+    }
+
+    async fn stop_button(&mut self) -> Result<bool> {
+        let mut driver = self.driver.lock().await;
+        let result = driver.stop_button().await;
+        self.update(&driver);
+        result
+    }
+
+    async fn obstruction_switch(&mut self) -> Result<bool> {
+        let mut driver = self.driver.lock().await;
+        let result = driver.obstruction_switch().await;
+        self.update(&driver);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MockDriver;
+
+    #[tokio::test]
+    async fn clones_share_the_driver() {
+        let mut outputs = SharedDriver::new(MockDriver::default());
+        let mut inputs = outputs.clone();
+
+        outputs.motor_direction(Direction::Up).await.unwrap();
+        assert_eq!(inputs.lock().await.motor, Some(Direction::Up));
+
+        inputs.lock().await.disconnected = true;
+        assert!(inputs.floor().await.is_err());
+        assert!(!outputs.is_connected());
+    }
+}
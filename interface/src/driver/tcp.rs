@@ -0,0 +1,296 @@
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind, Result};
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::time::Instant;
+
+use crate::client::Client;
+use crate::types::{Button, Direction, Floor};
+use crate::{get, send};
+
+use super::HardwareDriver;
+
+const MIN_BACKOFF: Duration = Duration::from_millis(100);
+const MAX_BACKOFF: Duration = Duration::from_secs(5);
+
+/// Connection to an elevator server, which reconnects by itself when the connection is lost
+///
+/// Every call that fails drops the connection, see check(). The next call
+/// reconnects, backing off between failed attempts, and sends the last known
+/// outputs (lights, floor indicator and motor direction) to the elevator again
+/// before going on. Calls made while waiting to reconnect fail with
+/// ErrorKind::NotConnected.
+#[derive(Debug)]
+pub struct ElevatorDriver {
+    addr: SocketAddr,
+    client: Option<Client>,
+    outputs: Outputs,
+    backoff: Duration,
+    retry_at: Instant,
+}
+
+/// Last value sent to each output of the elevator
+#[derive(Debug, Default)]
+struct Outputs {
+    motor: Option<Direction>,
+    order_lights: HashMap<(Button, Floor), bool>,
+    floor_indicator: Option<Floor>,
+    door_open_light: bool,
+    stop_button_light: bool,
+}
+
+impl ElevatorDriver {
+    pub async fn connect(addr: SocketAddr) -> Result<Self> {
+        let client = Client::connect(addr).await?;
+        Ok(ElevatorDriver {
+            addr,
+            client: Some(client),
+            outputs: Outputs::default(),
+            backoff: MIN_BACKOFF,
+            retry_at: Instant::now(),
+        })
+    }
+
+    /// Returns the current connection, reconnecting first if it was lost
+    async fn client(&mut self) -> Result<&mut Client> {
+        if self.client.is_none() {
+            self.reconnect().await?;
+        }
+        Ok(self.client.as_mut().unwrap())
+    }
+
+    async fn reconnect(&mut self) -> Result<()> {
+        if Instant::now() < self.retry_at {
+            let msg = format!("lost connection to {}, waiting to reconnect", self.addr);
+            return Err(Error::new(ErrorKind::NotConnected, msg));
+        }
+
+        let result = match Client::connect(self.addr).await {
+            Ok(mut client) => self.outputs.restore(&mut client).await.map(|_| client),
+            Err(e) => Err(e),
+        };
+
+        match result {
+            Ok(client) => {
+                println!("Reconnected to elevator at {}", self.addr);
+                self.client = Some(client);
+                self.backoff = MIN_BACKOFF;
+                Ok(())
+            }
+            Err(e) => {
+                self.retry_at = Instant::now() + self.backoff;
+                self.backoff = (self.backoff * 2).min(MAX_BACKOFF);
+                Err(e)
+            }
+        }
+    }
+
+    /// Drops the connection if the call failed, so the next call reconnects
+    ///
+    /// Only a command that could not be sent at all (InvalidInput) keeps the
+    /// connection. Any other error, including a response that could not be decoded,
+    /// may leave responses unread in the stream, which later queries would get instead
+    /// of their own.
+    fn check<T>(&mut self, result: Result<T>) -> Result<T> {
+        if let Err(e) = &result {
+            if e.kind() != ErrorKind::InvalidInput && self.client.take().is_some() {
+                eprintln!("Lost connection to elevator at {} => error: {e}", self.addr);
+                self.retry_at = Instant::now();
+            }
+        }
+        result
+    }
+}
+
+impl HardwareDriver for ElevatorDriver {
+    fn is_connected(&self) -> bool {
+        self.client.is_some()
+    }
+
+    async fn motor_direction(&mut self, direction: Direction) -> Result<()> {
+        self.outputs.motor = Some(direction);
+        let client = self.client().await?;
+        let result = send::motor_direction(client, direction).await;
+        self.check(result)
+    }
+
+    async fn stop(&mut self) -> Result<()> {
+        self.outputs.motor = None;
+        let client = self.client().await?;
+        let result = send::stop(client).await;
+        self.check(result)
+    }
+
+    async fn order_button_light(&mut self, button: Button, floor: Floor, on: bool) -> Result<()> {
+        self.outputs.order_lights.insert((button, floor), on);
+        let client = self.client().await?;
+        let result = send::order_button_light(client, button, floor, on).await;
+        self.check(result)
+    }
+
+    async fn floor_indicator(&mut self, floor: Floor) -> Result<()> {
+        self.outputs.floor_indicator = Some(floor);
+        let client = self.client().await?;
+        let result = send::floor_indicator(client, floor).await;
+        self.check(result)
+    }
+
+    async fn door_open_light(&mut self, on: bool) -> Result<()> {
+        self.outputs.door_open_light = on;
+        let client = self.client().await?;
+        let result = send::door_open_light(client, on).await;
+        self.check(result)
+    }
+
+    async fn stop_button_light(&mut self, on: bool) -> Result<()> {
+        self.outputs.stop_button_light = on;
+        let client = self.client().await?;
+        let result = send::stop_button_light(client, on).await;
+        self.check(result)
+    }
+
+    async fn order_button(&mut self, button: Button, floor: Floor) -> Result<bool> {
+        let client = self.client().await?;
+        let result = get::order_button(client, button, floor).await;
+        self.check(result)
+    }
+
+    async fn order_buttons(&mut self, buttons: &[(Button, Floor)]) -> Result<Vec<bool>> {
+        let client = self.client().await?;
+        let result = get::order_buttons(client, buttons).await;
+        self.check(result)
+    }
+
+    async fn floor(&mut self) -> Result<Option<Floor>> {
+        let client = self.client().await?;
+        let result = get::floor(client).await;
+        self.check(result)
+    }
+
+    async fn stop_button(&mut self) -> Result<bool> {
+        let client = self.client().await?;
+        let result = get::stop(client).await;
+        self.check(result)
+    }
+
+    async fn obstruction_switch(&mut self) -> Result<bool> {
+        let client = self.client().await?;
+        let result = get::obstruction_switch(client).await;
+        self.check(result)
+    }
+}
+
+impl Outputs {
+    /// Sends every known output to a new connection
+    async fn restore(&self, client: &mut Client) -> Result<()> {
+        for (&(button, floor), &on) in self.order_lights.iter() {
+            send::order_button_light(client, button, floor, on).await?;
+        }
+        if let Some(floor) = self.floor_indicator {
+            send::floor_indicator(client, floor).await?;
+        }
+        send::door_open_light(client, self.door_open_light).await?;
+        send::stop_button_light(client, self.stop_button_light).await?;
+        match self.motor {
+            Some(direction) => send::motor_direction(client, direction).await,
+            None => send::stop(client).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+
+    use super::*;
+    use crate::types::floor::N_FLOORS;
+
+    async fn read_command(stream: &mut TcpStream) -> [u8; 4] {
+        let mut buffer = [0; 4];
+        stream.read_exact(&mut buffer).await.unwrap();
+        buffer
+    }
+
+    #[tokio::test]
+    async fn restores_outputs_after_reconnecting() {
+        N_FLOORS.get_or_init(|| 4);
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let mut driver = ElevatorDriver::connect(addr).await.unwrap();
+        let (mut stream, _) = listener.accept().await.unwrap();
+
+        driver.floor_indicator(Floor::from(2)).await.unwrap();
+        driver.motor_direction(Direction::Up).await.unwrap();
+        assert_eq!(read_command(&mut stream).await, [3, 2, 0, 0]);
+        assert_eq!(read_command(&mut stream).await, [1, 1, 0, 0]);
+
+        // The simulator restarts, and the connection is lost
+        drop(stream);
+        assert!(driver.floor().await.is_err());
+        assert!(!driver.is_connected());
+
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut commands = Vec::new();
+            loop {
+                let command = read_command(&mut stream).await;
+                if command[0] == 7 {
+                    stream.write_all(&[7, 1, 2, 0]).await.unwrap();
+                    return commands;
+                }
+                commands.push(command);
+            }
+        });
+
+        assert_eq!(driver.floor().await.unwrap(), Some(Floor::from(2)));
+        assert!(driver.is_connected());
+
+        let commands = server.await.unwrap();
+        assert!(commands.contains(&[3, 2, 0, 0]));
+        assert!(commands.contains(&[4, 0, 0, 0]));
+        assert_eq!(commands.last(), Some(&[1, 1, 0, 0]));
+    }
+
+    #[tokio::test]
+    async fn reconnects_after_a_malformed_response() {
+        N_FLOORS.get_or_init(|| 4);
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let mut driver = ElevatorDriver::connect(addr).await.unwrap();
+        let (mut stream, _) = listener.accept().await.unwrap();
+
+        // The first of three responses is garbled, leaving the other two unread
+        let buttons = [Button::Cab, Button::Hall(Direction::Up), Button::Cab];
+        let floors = [0, 0, 1].map(Floor::from);
+        let queries: Vec<_> = buttons.into_iter().zip(floors).collect();
+        let server = tokio::spawn(async move {
+            for _ in 0..3 {
+                read_command(&mut stream).await;
+            }
+            stream
+                .write_all(&[6, 5, 0, 0, 6, 0, 0, 0, 6, 1, 0, 0])
+                .await
+                .unwrap();
+            stream
+        });
+        let error = driver.order_buttons(&queries).await.unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        assert!(!driver.is_connected());
+        let _stale = server.await.unwrap();
+
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            while read_command(&mut stream).await[0] != 7 {}
+            stream.write_all(&[7, 1, 1, 0]).await.unwrap();
+            stream
+        });
+        assert_eq!(driver.floor().await.unwrap(), Some(Floor::from(1)));
+        assert!(driver.is_connected());
+        server.await.unwrap();
+    }
+}
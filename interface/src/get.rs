@@ -1,112 +1,63 @@
 use std::io::{Error, ErrorKind, Result};
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::TcpStream;
 
+use crate::client::Client;
+use crate::protocol::{Command, Response};
 use crate::types::{Button, Floor};
 
-async fn get_data(stream: &mut TcpStream, buffer: &mut [u8; 4]) -> Result<()> {
-    stream.write_all(buffer).await?;
-    *buffer = [0; 4];
-    stream.read(buffer).await?;
-    Ok(())
-}
-
-pub async fn order_button(stream: &mut TcpStream, button: Button, floor: Floor) -> Result<bool> {
-    let mut buffer: [u8; 4] = [6, u8::from(button), u8::from(floor), 0];
-    get_data(stream, &mut buffer).await?;
-
-    if buffer[0] != 6 {
-        return Err(Error::new(
-            ErrorKind::InvalidData,
-            "incorrect feedback for buffer[0] in floor()",
-        ));
+pub async fn order_button(client: &mut Client, button: Button, floor: Floor) -> Result<bool> {
+    let floor = u8::from(floor);
+    match client.query(Command::OrderButton { button, floor }).await? {
+        Response::OrderButton(pressed) => Ok(pressed),
+        response => Err(unexpected("order_button", response)),
     }
-
-    let pressed = match buffer[1] {
-        0 => false,
-        1 => true,
-        _ => {
-            return Err(Error::new(
-                ErrorKind::InvalidData,
-                "incorrect feedback for buffer[1] in order_button()",
-            ))
-        }
-    };
-
-    Ok(pressed)
 }
 
-pub async fn floor(stream: &mut TcpStream) -> Result<Option<Floor>> {
-    let mut buffer: [u8; 4] = [7, 0, 0, 0];
-    get_data(stream, &mut buffer).await?;
+/// Reads several buttons with a single round trip
+pub async fn order_buttons(client: &mut Client, buttons: &[(Button, Floor)]) -> Result<Vec<bool>> {
+    let queries: Vec<Command> = buttons
+        .iter()
+        .map(|&(button, floor)| Command::OrderButton {
+            button,
+            floor: u8::from(floor),
+        })
+        .collect();
+
+    let responses = client.query_all(&queries).await?;
+    responses
+        .into_iter()
+        .map(|response| match response {
+            Response::OrderButton(pressed) => Ok(pressed),
+            response => Err(unexpected("order_buttons", response)),
+        })
+        .collect()
+}
 
-    if buffer[0] != 7 {
-        return Err(Error::new(
-            ErrorKind::InvalidData,
-            "incorrect feedback for buffer[0] in floor()",
-        ));
+pub async fn floor(client: &mut Client) -> Result<Option<Floor>> {
+    match client.query(Command::Floor).await? {
+        Response::Floor(None) => Ok(None),
+        Response::Floor(Some(floor)) => Floor::try_from(floor).map(Some).map_err(|_| {
+            let msg = format!("floor {floor} is out of range in floor()");
+            Error::new(ErrorKind::InvalidData, msg)
+        }),
+        response => Err(unexpected("floor", response)),
     }
-
-    let floor = match buffer[1] {
-        0 => None,
-        1 => Floor::from_value(buffer[2] as usize),
-        _ => {
-            return Err(Error::new(
-                ErrorKind::InvalidData,
-                "incorrect feedback for buffer[1] in floor()",
-            ))
-        }
-    };
-
-    Ok(floor)
 }
 
-pub async fn stop(stream: &mut TcpStream) -> Result<bool> {
-    let mut buffer: [u8; 4] = [8, 0, 0, 0];
-    get_data(stream, &mut buffer).await?;
-
-    if buffer[0] != 8 {
-        return Err(Error::new(
-            ErrorKind::InvalidData,
-            "incorrect feedback for buffer[0] in stop()",
-        ));
+pub async fn stop(client: &mut Client) -> Result<bool> {
+    match client.query(Command::StopButton).await? {
+        Response::StopButton(pressed) => Ok(pressed),
+        response => Err(unexpected("stop", response)),
     }
-
-    let pressed = match buffer[1] {
-        0 => false,
-        1 => true,
-        _ => {
-            return Err(Error::new(
-                ErrorKind::InvalidData,
-                "incorrect feedback for buffer[1] in stop()",
-            ))
-        }
-    };
-
-    Ok(pressed)
 }
 
-pub async fn obstruction_switch(stream: &mut TcpStream) -> Result<bool> {
-    let mut buffer: [u8; 4] = [9, 0, 0, 0];
-    get_data(stream, &mut buffer).await?;
-
-    if buffer[0] != 9 {
-        return Err(Error::new(
-            ErrorKind::InvalidData,
-            "incorrect feedback for buffer[0] in obstruction_switch()",
-        ));
+pub async fn obstruction_switch(client: &mut Client) -> Result<bool> {
+    match client.query(Command::Obstruction).await? {
+        Response::Obstruction(active) => Ok(active),
+        response => Err(unexpected("obstruction_switch", response)),
     }
+}
 
-    let active = match buffer[1] {
-        0 => false,
-        1 => true,
-        _ => {
-            return Err(Error::new(
-                ErrorKind::InvalidData,
-                "incorrect feedback for buffer[1] in stop()",
-            ))
-        }
-    };
-
-    Ok(active)
+fn unexpected(function: &str, response: Response) -> Error {
+    let msg = format!("unexpected {response:?} in {function}()");
+    Error::new(ErrorKind::InvalidData, msg)
 }
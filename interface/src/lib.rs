@@ -1,7 +1,13 @@
+pub mod client;
+pub mod driver;
 pub mod get;
+pub mod protocol;
 pub mod send;
 pub mod types;
 
+pub use crate::client::Client;
+pub use crate::driver::{Edge, Edges, ElevatorDriver, HardwareDriver, MockDriver, SharedDriver};
+
 #[cfg(test)]
 mod tests {
     use std::net::SocketAddr;
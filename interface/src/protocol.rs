@@ -0,0 +1,287 @@
+use std::fmt;
+
+use crate::types::{Button, Direction};
+
+/// Message sent to the elevator server, encoded as 4 bytes with the opcode first
+///
+/// Floors are sent as is, it is up to the server to check them against its number
+/// of floors. Every query (OrderButton, Floor, StopButton and Obstruction) is
+/// answered with a Response with the same opcode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    ReloadConfig,
+    /// Runs the motor in the given direction, or stops it
+    MotorDirection(Option<Direction>),
+    OrderButtonLight {
+        button: Button,
+        floor: u8,
+        on: bool,
+    },
+    FloorIndicator(u8),
+    DoorOpenLight(bool),
+    StopButtonLight(bool),
+    OrderButton {
+        button: Button,
+        floor: u8,
+    },
+    Floor,
+    StopButton,
+    Obstruction,
+}
+
+/// Answer to a query, encoded as 4 bytes with the opcode of the query first
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Response {
+    OrderButton(bool),
+    /// The floor the car is at, or None if it is between floors
+    Floor(Option<u8>),
+    StopButton(bool),
+    Obstruction(bool),
+}
+
+/// A message that could not be decoded, because of the byte at `index`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodeError {
+    pub message: [u8; 4],
+    pub index: usize,
+    pub expected: &'static str,
+}
+
+impl Command {
+    pub fn opcode(&self) -> u8 {
+        match self {
+            Command::ReloadConfig => 0,
+            Command::MotorDirection(_) => 1,
+            Command::OrderButtonLight { .. } => 2,
+            Command::FloorIndicator(_) => 3,
+            Command::DoorOpenLight(_) => 4,
+            Command::StopButtonLight(_) => 5,
+            Command::OrderButton { .. } => 6,
+            Command::Floor => 7,
+            Command::StopButton => 8,
+            Command::Obstruction => 9,
+        }
+    }
+
+    /// Whether the server answers the command with a Response
+    pub fn is_query(&self) -> bool {
+        self.opcode() >= 6
+    }
+
+    pub fn encode(&self) -> [u8; 4] {
+        let opcode = self.opcode();
+        match *self {
+            Command::MotorDirection(direction) => [opcode, direction.map_or(0, u8::from), 0, 0],
+            Command::OrderButtonLight { button, floor, on } => {
+                [opcode, u8::from(button), floor, u8::from(on)]
+            }
+            Command::FloorIndicator(floor) => [opcode, floor, 0, 0],
+            Command::DoorOpenLight(on) | Command::StopButtonLight(on) => {
+                [opcode, u8::from(on), 0, 0]
+            }
+            Command::OrderButton { button, floor } => [opcode, u8::from(button), floor, 0],
+            Command::ReloadConfig | Command::Floor | Command::StopButton | Command::Obstruction => {
+                [opcode, 0, 0, 0]
+            }
+        }
+    }
+
+    pub fn decode(message: [u8; 4]) -> Result<Command, DecodeError> {
+        let error = |index, expected| DecodeError {
+            message,
+            index,
+            expected,
+        };
+        let button = || Button::try_from(message[1]).map_err(|_| error(1, "a button (0-2)"));
+
+        let command = match message[0] {
+            0 => Command::ReloadConfig,
+            1 => Command::MotorDirection(match message[1] {
+                0 => None,
+                1 => Some(Direction::Up),
+                255 => Some(Direction::Down),
+                _ => return Err(error(1, "a motor direction (0, 1 or 255)")),
+            }),
+            2 => Command::OrderButtonLight {
+                button: button()?,
+                floor: message[2],
+                on: decode_bool(message, 3).map_err(|_| error(3, "0 or 1"))?,
+            },
+            3 => Command::FloorIndicator(message[1]),
+            4 => Command::DoorOpenLight(decode_bool(message, 1).map_err(|_| error(1, "0 or 1"))?),
+            5 => Command::StopButtonLight(decode_bool(message, 1).map_err(|_| error(1, "0 or 1"))?),
+            6 => Command::OrderButton {
+                button: button()?,
+                floor: message[2],
+            },
+            7 => Command::Floor,
+            8 => Command::StopButton,
+            9 => Command::Obstruction,
+            _ => return Err(error(0, "a command opcode (0-9)")),
+        };
+        Ok(command)
+    }
+}
+
+impl Response {
+    pub fn opcode(&self) -> u8 {
+        match self {
+            Response::OrderButton(_) => 6,
+            Response::Floor(_) => 7,
+            Response::StopButton(_) => 8,
+            Response::Obstruction(_) => 9,
+        }
+    }
+
+    pub fn encode(&self) -> [u8; 4] {
+        let opcode = self.opcode();
+        match *self {
+            Response::Floor(Some(floor)) => [opcode, 1, floor, 0],
+            Response::Floor(None) => [opcode, 0, 0, 0],
+            Response::OrderButton(on) | Response::StopButton(on) | Response::Obstruction(on) => {
+                [opcode, u8::from(on), 0, 0]
+            }
+        }
+    }
+
+    pub fn decode(message: [u8; 4]) -> Result<Response, DecodeError> {
+        let error = |index, expected| DecodeError {
+            message,
+            index,
+            expected,
+        };
+        let flag = || decode_bool(message, 1).map_err(|_| error(1, "0 or 1"));
+
+        let response = match message[0] {
+            6 => Response::OrderButton(flag()?),
+            7 => Response::Floor(flag()?.then_some(message[2])),
+            8 => Response::StopButton(flag()?),
+            9 => Response::Obstruction(flag()?),
+            _ => return Err(error(0, "a response opcode (6-9)")),
+        };
+        Ok(response)
+    }
+}
+
+fn decode_bool(message: [u8; 4], index: usize) -> Result<bool, ()> {
+    match message[index] {
+        0 => Ok(false),
+        1 => Ok(true),
+        _ => Err(()),
+    }
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "invalid message {:?}: byte {} is {}, expected {}",
+            self.message, self.index, self.message[self.index], self.expected
+        )
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+impl From<DecodeError> for std::io::Error {
+    fn from(error: DecodeError) -> Self {
+        std::io::Error::new(std::io::ErrorKind::InvalidData, error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commands() -> Vec<Command> {
+        let mut commands = vec![
+            Command::ReloadConfig,
+            Command::MotorDirection(None),
+            Command::MotorDirection(Some(Direction::Up)),
+            Command::MotorDirection(Some(Direction::Down)),
+            Command::Floor,
+            Command::StopButton,
+            Command::Obstruction,
+        ];
+        for on in [false, true] {
+            commands.push(Command::DoorOpenLight(on));
+            commands.push(Command::StopButtonLight(on));
+        }
+        for floor in 0..=u8::MAX {
+            commands.push(Command::FloorIndicator(floor));
+            for button in Button::iterator() {
+                commands.push(Command::OrderButton { button, floor });
+                for on in [false, true] {
+                    commands.push(Command::OrderButtonLight { button, floor, on });
+                }
+            }
+        }
+        commands
+    }
+
+    fn responses() -> Vec<Response> {
+        let mut responses = vec![Response::Floor(None)];
+        for on in [false, true] {
+            responses.push(Response::OrderButton(on));
+            responses.push(Response::StopButton(on));
+            responses.push(Response::Obstruction(on));
+        }
+        responses.extend((0..=u8::MAX).map(|floor| Response::Floor(Some(floor))));
+        responses
+    }
+
+    #[test]
+    fn commands_round_trip() {
+        for command in commands() {
+            let message = command.encode();
+            assert_eq!(message[0], command.opcode());
+            assert_eq!(Command::decode(message), Ok(command));
+        }
+    }
+
+    #[test]
+    fn responses_round_trip() {
+        for response in responses() {
+            let message = response.encode();
+            assert_eq!(message[0], response.opcode());
+            assert_eq!(Response::decode(message), Ok(response));
+        }
+    }
+
+    #[test]
+    fn every_decoded_message_encodes_back() {
+        for opcode in 0..=10 {
+            for byte1 in 0..=u8::MAX {
+                for byte2 in [0, 1, 3, 255] {
+                    for byte3 in [0, 1, 2] {
+                        let message = [opcode, byte1, byte2, byte3];
+                        if let Ok(command) = Command::decode(message) {
+                            assert_eq!(Command::decode(command.encode()), Ok(command));
+                        }
+                        if let Ok(response) = Response::decode(message) {
+                            assert_eq!(Response::decode(response.encode()), Ok(response));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn decode_errors_name_the_byte() {
+        let error = Command::decode([42, 0, 0, 0]).unwrap_err();
+        assert_eq!(error.index, 0);
+        let error = Command::decode([2, 7, 1, 1]).unwrap_err();
+        assert_eq!(error.index, 1);
+        assert_eq!(
+            error.to_string(),
+            "invalid message [2, 7, 1, 1]: byte 1 is 7, expected a button (0-2)"
+        );
+        let error = Command::decode([2, 0, 1, 5]).unwrap_err();
+        assert_eq!(error.index, 3);
+        assert_eq!(Command::decode([1, 2, 0, 0]).unwrap_err().index, 1);
+
+        assert_eq!(Response::decode([5, 0, 0, 0]).unwrap_err().index, 0);
+        assert_eq!(Response::decode([7, 2, 1, 0]).unwrap_err().index, 1);
+    }
+}
@@ -1,3 +1,5 @@
+use serde::{Deserialize, Serialize};
+
 pub mod button;
 pub mod direction;
 pub mod floor;
@@ -6,7 +8,7 @@ pub mod floor;
 /// Button::Hall(Direction::Up) <==> 0
 /// Button::Hall(Direction::Down) <==> 1
 /// Button::Cab <==> 2
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Button {
     Hall(Direction),
     Cab,
@@ -15,7 +17,7 @@ pub enum Button {
 /// Type representation for direction values
 /// Direction::Up <==> 1
 /// Direction::Down <==> -1 (255)
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Direction {
     Up,
     Down,
@@ -1,7 +1,7 @@
 use crate::types::Direction;
 
 impl Direction {
-    fn to_str(&self) -> &str {
+    fn to_str(self) -> &'static str {
         match self {
             Direction::Up => "up",
             Direction::Down => "down",
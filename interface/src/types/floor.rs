@@ -1,9 +1,11 @@
 use once_cell::sync::OnceCell;
+use serde::de::{Deserializer, Error};
+use serde::{Deserialize, Serialize, Serializer};
 
 use crate::types::Floor;
 
 pub static N_FLOORS: OnceCell<usize> = OnceCell::new();
-static ERROR_MESSAGE: &'static str =
+static ERROR_MESSAGE: &str =
     "Floor is uninitialized, run Floor::initialize(max_floor) first.";
 
 impl Floor {
@@ -32,8 +34,10 @@ impl Floor {
         floor.ok_or(self.max)
     } 
 
+    /// Sets the number of floors, which can not be changed once set
     pub fn initialize(n_floors: usize) {
-        N_FLOORS.set(n_floors).unwrap();
+        let n = *N_FLOORS.get_or_init(|| n_floors);
+        assert_eq!(n, n_floors, "Floor is already initialized with {n} floors");
     }
 
     pub fn get_n_floors() -> usize {
@@ -41,6 +45,12 @@ impl Floor {
     }
 }
 
+impl Default for Floor {
+    fn default() -> Self {
+        Floor::new()
+    }
+}
+
 impl From<usize> for Floor {
     fn from(val: usize) -> Floor {
         Floor::from_value(val).unwrap()
@@ -72,3 +82,18 @@ impl std::fmt::Display for Floor {
         write!(f, "{}/{}", self.val, self.max)
     }
 }
+
+/// Floors are serialized as their value only, the max value is taken from N_FLOORS
+impl Serialize for Floor {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(self.val as u64)
+    }
+}
+
+impl<'de> Deserialize<'de> for Floor {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let val = usize::deserialize(deserializer)?;
+        Floor::from_value(val)
+            .ok_or_else(|| D::Error::custom(format!("floor {val} is out of range")))
+    }
+}
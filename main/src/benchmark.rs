@@ -0,0 +1,417 @@
+use std::time::Duration;
+use tokio::sync::mpsc::{self, Sender};
+
+use serde::Serialize;
+
+use interface::types::{Button, Direction, Floor};
+
+use crate::config::{CostWeights, Strategy, Timing};
+use crate::dispatcher::assigner::{self, Assigner, Call, Candidate};
+use crate::state_machine::simulate::{self, Step};
+use crate::types::{Elevator, Message, TaskInfo};
+
+mod traffic;
+
+pub use self::traffic::Traffic;
+
+use self::traffic::Passenger;
+
+/// Energy used to start and stop the car once, in units of the energy used to travel
+/// one floor
+const START_ENERGY: f64 = 1.0;
+
+/// Time the elevators get to deliver the passengers left when the traffic stops
+const DRAIN_TIME: Duration = Duration::from_secs(3600);
+
+/// Discrete-event simulation of a group of elevators serving passenger traffic, used
+/// to compare the assigners without any hardware
+///
+/// The cars make the same decisions as the state machine, see simulate::step(), and the
+/// hall requests are handed out by the assigner every time a car changes, like the
+/// dispatcher does. The door is never obstructed, and the cars have no capacity limit.
+/// Floor::initialize() has to be called before running it.
+#[derive(Debug, Clone)]
+pub struct Benchmark {
+    pub n_elevators: usize,
+    pub timing: Timing,
+    pub cost: CostWeights,
+    pub traffic: Traffic,
+    /// Passengers arriving each minute, on average
+    pub rate: f64,
+    /// Time passengers keep arriving
+    pub duration: Duration,
+    pub seed: u64,
+}
+
+/// Results of one simulation
+#[derive(Debug, Clone, Serialize)]
+pub struct Summary {
+    pub assigner: Strategy,
+    pub traffic: Traffic,
+    pub passengers: usize,
+    pub delivered: usize,
+    /// Time from showing up until boarding, of the delivered passengers
+    pub average_wait_secs: f64,
+    pub max_wait_secs: f64,
+    /// Time from showing up until arriving at the destination
+    pub average_journey_secs: f64,
+    pub floors_travelled: usize,
+    pub starts: usize,
+    /// Floors travelled plus START_ENERGY for every start
+    pub energy: f64,
+}
+
+struct Car {
+    elevator: Elevator,
+    /// Time of the next decision, None while idle without requests
+    next: Option<Duration>,
+    riders: Vec<usize>,
+}
+
+/// Times a passenger boarded and got off, if they did
+#[derive(Default)]
+struct Trip {
+    boarded: Option<Duration>,
+    delivered: Option<Duration>,
+}
+
+enum Event {
+    /// A car opened its door at a floor, serving it in a direction
+    Door(usize, Floor, Direction),
+    Arrival(usize),
+    Decision(usize),
+}
+
+struct Simulation {
+    assigner: Box<dyn Assigner>,
+    /// Never used, only needed to build the TaskInfo given to the assigner
+    transmitter: Sender<Message>,
+    cars: Vec<Car>,
+    passengers: Vec<Passenger>,
+    trips: Vec<Trip>,
+    /// Passengers that have shown up, but not boarded yet
+    waiting: Vec<usize>,
+    calls: Vec<Call>,
+    doors: Vec<(Duration, usize, Floor, Direction)>,
+    now: Duration,
+    floors_travelled: usize,
+    starts: usize,
+}
+
+impl Default for Benchmark {
+    fn default() -> Self {
+        Benchmark {
+            n_elevators: 3,
+            timing: Timing::default(),
+            cost: CostWeights::default(),
+            traffic: Traffic::default(),
+            rate: 6.0,
+            duration: Duration::from_secs(3600),
+            seed: 1,
+        }
+    }
+}
+
+impl Benchmark {
+    /// Runs the simulation with the given assigner, which always gives the same result
+    pub fn run(&self, strategy: Strategy) -> Summary {
+        let passengers = self.traffic.passengers(self.rate, self.duration, self.seed);
+        let mut simulation = Simulation {
+            assigner: assigner::build(strategy, self.timing, self.cost),
+            transmitter: mpsc::channel(1).0,
+            cars: (0..self.n_elevators)
+                .map(|_| Car {
+                    elevator: Elevator::new(Floor::from(0), self.timing),
+                    next: None,
+                    riders: Vec::new(),
+                })
+                .collect(),
+            trips: passengers.iter().map(|_| Trip::default()).collect(),
+            passengers,
+            waiting: Vec::new(),
+            calls: Vec::new(),
+            doors: Vec::new(),
+            now: Duration::ZERO,
+            floors_travelled: 0,
+            starts: 0,
+        };
+        simulation.run(self.duration + DRAIN_TIME);
+        simulation.summary(strategy, self.traffic)
+    }
+}
+
+impl Simulation {
+    /// Handles every event until all passengers are delivered, or `end` is reached
+    fn run(&mut self, end: Duration) {
+        let mut arrivals = 0..self.passengers.len();
+        let mut next_arrival = arrivals.next();
+        loop {
+            // Doors first and decisions last, so cars see everything that happened
+            // at the same time
+            let door = self.doors.iter().enumerate().min_by_key(|(_, door)| door.0);
+            let door = door.map(|(i, door)| (door.0, 0, Event::Door(door.1, door.2, door.3), i));
+            let arrival =
+                next_arrival.map(|i| (self.passengers[i].arrival, 1, Event::Arrival(i), 0));
+            let decision = self
+                .cars
+                .iter()
+                .enumerate()
+                .filter_map(|(id, car)| Some((car.next?, 2, Event::Decision(id), 0)))
+                .min_by_key(|event| (event.0, event.1));
+
+            let Some((time, _, event, index)) = [door, arrival, decision]
+                .into_iter()
+                .flatten()
+                .min_by_key(|event| (event.0, event.1))
+            else {
+                return;
+            };
+            if time > end {
+                return;
+            }
+            self.now = time;
+
+            match event {
+                Event::Door(id, floor, direction) => {
+                    self.doors.swap_remove(index);
+                    self.open_door(id, floor, direction);
+                }
+                Event::Arrival(i) => {
+                    next_arrival = arrivals.next();
+                    self.arrive(i);
+                }
+                Event::Decision(id) => self.decide(id),
+            }
+            self.distribute();
+        }
+    }
+
+    /// Passenger `i` shows up, and presses the hall button unless it is already lit
+    fn arrive(&mut self, i: usize) {
+        let passenger = self.passengers[i];
+        self.waiting.push(i);
+        let (floor, direction) = (passenger.origin, passenger.direction());
+        if !self
+            .calls
+            .iter()
+            .any(|call| call.floor == floor && call.direction == direction)
+        {
+            self.calls.push(Call {
+                floor,
+                direction,
+                owner: None,
+            });
+        }
+    }
+
+    /// Lets the car make decisions until it is busy moving or with the door open
+    fn decide(&mut self, id: usize) {
+        let car = &mut self.cars[id];
+        let timing = car.elevator.timing;
+        car.next = None;
+        while let Some(step) = simulate::step(&mut car.elevator) {
+            let floor = car.elevator.floor;
+            match step {
+                Step::Pass => self.floors_travelled += 1,
+                Step::Arrive(direction) => {
+                    self.floors_travelled += 1;
+                    let opens = self.now + timing.travel_time;
+                    self.doors.push((opens, id, floor, direction));
+                }
+                Step::Open(direction) => self.doors.push((self.now, id, floor, direction)),
+                Step::Depart(_) => self.starts += 1,
+                Step::Close => {}
+            }
+
+            // The hall light goes out as soon as the car decides to stop
+            if let Step::Arrive(direction) | Step::Open(direction) = step {
+                self.calls
+                    .retain(|call| (call.floor, call.direction) != (floor, direction));
+            }
+
+            let duration = step.duration(&timing);
+            if !duration.is_zero() {
+                car.next = Some(self.now + duration);
+                return;
+            }
+        }
+    }
+
+    /// Lets the riders for this floor off, and everyone waiting to go in the direction on
+    fn open_door(&mut self, id: usize, floor: Floor, direction: Direction) {
+        let now = self.now;
+        self.calls
+            .retain(|call| (call.floor, call.direction) != (floor, direction));
+
+        let car = &mut self.cars[id];
+        let trips = &mut self.trips;
+        let passengers = &self.passengers;
+        car.riders.retain(|&i| {
+            let arrived = passengers[i].destination == floor;
+            if arrived {
+                trips[i].delivered = Some(now);
+            }
+            !arrived
+        });
+
+        self.waiting.retain(|&i| {
+            let passenger = passengers[i];
+            let boards = passenger.origin == floor && passenger.direction() == direction;
+            if boards {
+                trips[i].boarded = Some(now);
+                car.riders.push(i);
+                car.elevator
+                    .requests
+                    .add_request(Button::Cab, passenger.destination);
+            }
+            !boards
+        });
+    }
+
+    /// Hands out the hall calls with the assigner, and gives each car its share
+    fn distribute(&mut self) {
+        if self.calls.is_empty() && self.cars.iter().all(|car| car.next.is_some()) {
+            return;
+        }
+
+        let tasks: Vec<TaskInfo> = self
+            .cars
+            .iter()
+            .enumerate()
+            .map(|(id, car)| {
+                let mut task = TaskInfo::new(id, self.transmitter.clone());
+                task.floor = car.elevator.floor;
+                task.state = car.elevator.state;
+                task.requests = car.elevator.requests.clone();
+                task
+            })
+            .collect();
+        let candidates: Vec<Candidate> = tasks
+            .iter()
+            .map(|task| Candidate {
+                task,
+                requests: task.cab_requests(),
+            })
+            .collect();
+        let owners = self.assigner.distribute(&candidates, &self.calls);
+
+        for (call, owner) in self.calls.iter_mut().zip(owners) {
+            call.owner = Some(owner);
+        }
+        for (id, car) in self.cars.iter_mut().enumerate() {
+            let hall_requests: Vec<(Floor, Direction)> = self
+                .calls
+                .iter()
+                .filter(|call| call.owner == Some(id))
+                .map(|call| (call.floor, call.direction))
+                .collect();
+            car.elevator.requests.set_hall_requests(&hall_requests);
+            if car.next.is_none() && car.elevator.requests.number_of_requests() > 0 {
+                car.next = Some(self.now);
+            }
+        }
+    }
+
+    fn summary(&self, assigner: Strategy, traffic: Traffic) -> Summary {
+        let delivered: Vec<(f64, f64)> = self
+            .trips
+            .iter()
+            .zip(&self.passengers)
+            .filter_map(|(trip, passenger)| {
+                let wait = trip.boarded? - passenger.arrival;
+                let journey = trip.delivered? - passenger.arrival;
+                Some((wait.as_secs_f64(), journey.as_secs_f64()))
+            })
+            .collect();
+        let average = |values: Vec<f64>| match values.len() {
+            0 => 0.0,
+            n => values.iter().sum::<f64>() / n as f64,
+        };
+
+        Summary {
+            assigner,
+            traffic,
+            passengers: self.passengers.len(),
+            delivered: delivered.len(),
+            average_wait_secs: average(delivered.iter().map(|d| d.0).collect()),
+            max_wait_secs: delivered.iter().map(|d| d.0).fold(0.0, f64::max),
+            average_journey_secs: average(delivered.iter().map(|d| d.1).collect()),
+            floors_travelled: self.floors_travelled,
+            starts: self.starts,
+            energy: self.floors_travelled as f64 + START_ENERGY * self.starts as f64,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use interface::types::floor::N_FLOORS;
+
+    use super::*;
+
+    fn benchmark(traffic: Traffic) -> Benchmark {
+        N_FLOORS.get_or_init(|| 4);
+        Benchmark {
+            n_elevators: 2,
+            traffic,
+            rate: 4.0,
+            duration: Duration::from_secs(600),
+            ..Benchmark::default()
+        }
+    }
+
+    #[test]
+    fn every_passenger_is_delivered() {
+        for traffic in Traffic::iterator() {
+            for strategy in Strategy::iterator() {
+                let summary = benchmark(traffic).run(strategy);
+                assert!(summary.passengers > 20, "{traffic} {strategy}");
+                assert_eq!(
+                    summary.delivered, summary.passengers,
+                    "{traffic} {strategy}"
+                );
+                assert!(summary.average_wait_secs > 0.0);
+                assert!(summary.average_journey_secs > summary.average_wait_secs);
+                assert!(summary.max_wait_secs >= summary.average_wait_secs);
+                assert!(summary.energy > summary.floors_travelled as f64);
+            }
+        }
+    }
+
+    #[test]
+    fn single_passenger() {
+        N_FLOORS.get_or_init(|| 4);
+        let timing = Timing::default();
+        let mut simulation = Simulation {
+            assigner: assigner::build(Strategy::TimeToIdle, timing, CostWeights::default()),
+            transmitter: mpsc::channel(1).0,
+            cars: vec![Car {
+                elevator: Elevator::new(Floor::from(2), timing),
+                next: None,
+                riders: Vec::new(),
+            }],
+            passengers: vec![Passenger {
+                arrival: Duration::from_secs(10),
+                origin: Floor::from(0),
+                destination: Floor::from(3),
+            }],
+            trips: vec![Trip::default()],
+            waiting: Vec::new(),
+            calls: Vec::new(),
+            doors: Vec::new(),
+            now: Duration::ZERO,
+            floors_travelled: 0,
+            starts: 0,
+        };
+        simulation.run(Duration::from_secs(3600));
+
+        // Two floors down to pick them up, and three floors up after the door closes
+        let summary = simulation.summary(Strategy::TimeToIdle, Traffic::Uniform);
+        let (travel, door) = (timing.travel_time, timing.door_open_time);
+        assert_eq!(summary.delivered, 1);
+        assert_eq!(summary.average_wait_secs, (travel * 2).as_secs_f64());
+        let journey = travel * 2 + door + travel * 3;
+        assert_eq!(summary.average_journey_secs, journey.as_secs_f64());
+        assert_eq!((summary.floors_travelled, summary.starts), (5, 2));
+    }
+}
@@ -0,0 +1,194 @@
+use std::str::FromStr;
+use std::time::Duration;
+
+use serde::Serialize;
+
+use interface::types::{Direction, Floor};
+
+/// Pattern of passenger traffic through the building, where floor 0 is the lobby
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Traffic {
+    /// Every trip is equally likely
+    #[default]
+    Uniform,
+    /// Morning rush, almost everyone goes up from the lobby
+    UpPeak,
+    /// Lunch break, people go both to and from the lobby
+    Lunch,
+    /// Evening rush, almost everyone goes down to the lobby
+    DownPeak,
+}
+
+/// Passenger who wants to go from one floor to another
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Passenger {
+    /// Time the passenger shows up at the origin, since the start of the traffic
+    pub arrival: Duration,
+    pub origin: Floor,
+    pub destination: Floor,
+}
+
+/// Small deterministic random number generator, so a seed always gives the same traffic
+pub struct Rng(u64);
+
+impl Traffic {
+    pub fn iterator() -> impl Iterator<Item = Traffic> {
+        [
+            Traffic::Uniform,
+            Traffic::UpPeak,
+            Traffic::Lunch,
+            Traffic::DownPeak,
+        ]
+        .iter()
+        .copied()
+    }
+
+    /// Share of the trips that start at the lobby, and share that end there, on top
+    /// of the uniform trips that happen to do so
+    fn lobby_shares(self) -> (f64, f64) {
+        match self {
+            Traffic::Uniform => (0.0, 0.0),
+            Traffic::UpPeak => (0.9, 0.0),
+            Traffic::Lunch => (0.4, 0.4),
+            Traffic::DownPeak => (0.0, 0.9),
+        }
+    }
+
+    /// Picks the origin and destination of a trip
+    fn trip(self, rng: &mut Rng, n_floors: usize) -> (usize, usize) {
+        let (from_lobby, to_lobby) = self.lobby_shares();
+        let upper_floor = |rng: &mut Rng| 1 + rng.below(n_floors - 1);
+
+        let x = rng.next_f64();
+        if x < from_lobby {
+            (0, upper_floor(rng))
+        } else if x < from_lobby + to_lobby {
+            (upper_floor(rng), 0)
+        } else {
+            let origin = rng.below(n_floors);
+            let destination = (origin + 1 + rng.below(n_floors - 1)) % n_floors;
+            (origin, destination)
+        }
+    }
+
+    /// Generates the passengers arriving within `duration`, on average `rate` of them
+    /// each minute, sorted by arrival
+    pub fn passengers(self, rate: f64, duration: Duration, seed: u64) -> Vec<Passenger> {
+        let n_floors = Floor::get_n_floors();
+        let mut rng = Rng::new(seed);
+        let mut passengers = Vec::new();
+        let mut time = 0.0;
+        loop {
+            // Arrivals are a Poisson process, so the time between them is exponential
+            time += -(1.0 - rng.next_f64()).ln() * 60.0 / rate;
+            if time >= duration.as_secs_f64() {
+                return passengers;
+            }
+            let (origin, destination) = self.trip(&mut rng, n_floors);
+            passengers.push(Passenger {
+                arrival: Duration::from_secs_f64(time),
+                origin: Floor::from(origin),
+                destination: Floor::from(destination),
+            });
+        }
+    }
+}
+
+impl Passenger {
+    pub fn direction(&self) -> Direction {
+        if usize::from(self.destination) > usize::from(self.origin) {
+            Direction::Up
+        } else {
+            Direction::Down
+        }
+    }
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        // SplitMix64
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in [0, 1)
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Uniform in 0..n
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+}
+
+impl FromStr for Traffic {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Traffic, String> {
+        match s {
+            "uniform" => Ok(Traffic::Uniform),
+            "up-peak" => Ok(Traffic::UpPeak),
+            "lunch" => Ok(Traffic::Lunch),
+            "down-peak" => Ok(Traffic::DownPeak),
+            _ => Err(format!("unknown traffic '{s}'")),
+        }
+    }
+}
+
+impl std::fmt::Display for Traffic {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let name = match self {
+            Traffic::Uniform => "uniform",
+            Traffic::UpPeak => "up-peak",
+            Traffic::Lunch => "lunch",
+            Traffic::DownPeak => "down-peak",
+        };
+        write!(f, "{name}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use interface::types::floor::N_FLOORS;
+
+    use super::*;
+
+    #[test]
+    fn peaks_go_through_the_lobby() {
+        N_FLOORS.get_or_init(|| 4);
+
+        let hour = Duration::from_secs(3600);
+        let lobby = Floor::from(0);
+        for traffic in Traffic::iterator() {
+            let passengers = traffic.passengers(10.0, hour, 7);
+            assert!((500..700).contains(&passengers.len()), "{traffic}");
+            assert!(passengers.iter().all(|p| p.origin != p.destination));
+            assert!(passengers.windows(2).all(|w| w[0].arrival <= w[1].arrival));
+
+            let share = |f: &dyn Fn(&Passenger) -> bool| {
+                passengers.iter().filter(|p| f(p)).count() as f64 / passengers.len() as f64
+            };
+            let from_lobby = share(&|p| p.origin == lobby);
+            let to_lobby = share(&|p| p.destination == lobby);
+            match traffic {
+                Traffic::Uniform => assert!(from_lobby < 0.4 && to_lobby < 0.4),
+                Traffic::UpPeak => assert!(from_lobby > 0.85),
+                Traffic::Lunch => assert!(from_lobby > 0.4 && to_lobby > 0.4),
+                Traffic::DownPeak => assert!(to_lobby > 0.85),
+            }
+        }
+
+        // Same seed, same traffic
+        let again = Traffic::Lunch.passengers(10.0, hour, 7);
+        assert_eq!(again, Traffic::Lunch.passengers(10.0, hour, 7));
+    }
+}
@@ -0,0 +1,165 @@
+use std::env;
+use std::process;
+use std::str::FromStr;
+use std::time::Duration;
+
+use elevators::{Benchmark, Strategy, Summary, Traffic};
+use interface::types::Floor;
+
+const USAGE: &str = "\
+Usage: benchmark [OPTIONS]
+
+Simulates passenger traffic without any hardware, and reports how well each assigner
+serves it, one row for each traffic pattern and assigner.
+
+Options:
+    --elevators <N>          Number of elevators [default: 3]
+    --floors <M>             Number of floors [default: 8]
+    --traffic <NAME,..>      Traffic patterns, any of: uniform, up-peak, lunch, down-peak
+                             [default: all of them]
+    --assigners <NAME,..>    Assigners, any of: time-to-idle, nearest, round-robin,
+                             least-loaded [default: all of them]
+    --rate <N>               Passengers arriving each minute, on average [default: 6]
+    --duration-secs <SECS>   Time passengers keep arriving [default: 3600]
+    --door-open-secs <SECS>  Time the door is kept open at a floor [default: 3]
+    --travel-time-secs <SECS>  Time to travel between two floors [default: 2]
+    --seed <N>               Seed of the traffic, the same seed gives the same passengers
+                             [default: 1]
+    --format <csv|json>      Output format [default: csv]
+    -h, --help               Print this message";
+
+const CSV_HEADER: &str = "assigner,traffic,passengers,delivered,average_wait_secs,\
+max_wait_secs,average_journey_secs,floors_travelled,starts,energy";
+
+enum Format {
+    Csv,
+    Json,
+}
+
+struct Options {
+    n_floors: usize,
+    benchmark: Benchmark,
+    traffic: Vec<Traffic>,
+    assigners: Vec<Strategy>,
+    format: Format,
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        println!("{USAGE}");
+        return;
+    }
+
+    let options = parse_args(&args).unwrap_or_else(|err| {
+        eprintln!("Problem parsing arguments: {err}");
+        eprintln!("{USAGE}");
+        process::exit(1);
+    });
+
+    Floor::initialize(options.n_floors);
+    let mut summaries = Vec::new();
+    for &traffic in &options.traffic {
+        let benchmark = Benchmark {
+            traffic,
+            ..options.benchmark.clone()
+        };
+        for &assigner in &options.assigners {
+            let summary = benchmark.run(assigner);
+            if let Format::Csv = options.format {
+                if summaries.is_empty() {
+                    println!("{CSV_HEADER}");
+                }
+                println!("{}", csv_row(&summary));
+            }
+            summaries.push(summary);
+        }
+    }
+
+    if let Format::Json = options.format {
+        println!("{}", serde_json::to_string_pretty(&summaries).unwrap());
+    }
+}
+
+fn csv_row(summary: &Summary) -> String {
+    format!(
+        "{},{},{},{},{:.2},{:.2},{:.2},{},{},{:.1}",
+        summary.assigner,
+        summary.traffic,
+        summary.passengers,
+        summary.delivered,
+        summary.average_wait_secs,
+        summary.max_wait_secs,
+        summary.average_journey_secs,
+        summary.floors_travelled,
+        summary.starts,
+        summary.energy,
+    )
+}
+
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut options = Options {
+        n_floors: 8,
+        benchmark: Benchmark::default(),
+        traffic: Traffic::iterator().collect(),
+        assigners: Strategy::iterator().collect(),
+        format: Format::Csv,
+    };
+    let benchmark = &mut options.benchmark;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let value = args
+            .next()
+            .ok_or(format!("missing value for argument '{arg}'"))?;
+        match arg.as_str() {
+            "--elevators" => benchmark.n_elevators = parse(arg, value)?,
+            "--floors" => options.n_floors = parse(arg, value)?,
+            "--traffic" => options.traffic = parse_list(value)?,
+            "--assigners" => options.assigners = parse_list(value)?,
+            "--rate" => benchmark.rate = parse(arg, value)?,
+            "--duration-secs" => benchmark.duration = parse_secs(arg, value)?,
+            "--door-open-secs" => benchmark.timing.door_open_time = parse_secs(arg, value)?,
+            "--travel-time-secs" => benchmark.timing.travel_time = parse_secs(arg, value)?,
+            "--seed" => benchmark.seed = parse(arg, value)?,
+            "--format" => {
+                options.format = match value.as_str() {
+                    "csv" => Format::Csv,
+                    "json" => Format::Json,
+                    _ => return Err(format!("unknown format '{value}'")),
+                }
+            }
+            _ => return Err(format!("unknown argument '{arg}'")),
+        }
+    }
+
+    if benchmark.n_elevators == 0 {
+        return Err("'--elevators' must be at least 1".into());
+    }
+    if options.n_floors < 2 {
+        return Err("'--floors' must be at least 2".into());
+    }
+    if benchmark.rate.is_nan() || benchmark.rate <= 0.0 {
+        return Err("'--rate' must be greater than zero".into());
+    }
+    if benchmark.timing.travel_time.is_zero() {
+        return Err("'--travel-time-secs' must be greater than zero".into());
+    }
+    Ok(options)
+}
+
+fn parse<T: FromStr>(arg: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value '{value}' for argument '{arg}'"))
+}
+
+fn parse_secs(arg: &str, value: &str) -> Result<Duration, String> {
+    let secs: f64 = parse(arg, value)?;
+    Duration::try_from_secs_f64(secs)
+        .map_err(|_| format!("invalid value '{value}' for argument '{arg}'"))
+}
+
+fn parse_list<T: FromStr<Err = String>>(value: &str) -> Result<Vec<T>, String> {
+    value.split(',').map(|name| name.trim().parse()).collect()
+}
@@ -0,0 +1,480 @@
+use std::env;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+mod file;
+mod overrides;
+
+use self::file::FileConfig;
+use self::overrides::Overrides;
+
+const DEFAULT_N_ELEVATORS: usize = 2;
+const DEFAULT_N_FLOORS: usize = 4;
+const DEFAULT_HOST: IpAddr = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
+const DEFAULT_BASE_PORT: u16 = 10000;
+const DEFAULT_DOOR_OPEN_TIME: Duration = Duration::from_secs(3);
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_millis(10);
+const DEFAULT_OBSTRUCTION_TIMEOUT: Duration = Duration::from_secs(10);
+const DEFAULT_TRAVEL_TIMEOUT: Duration = Duration::from_secs(5);
+const DEFAULT_TRAVEL_TIME: Duration = Duration::from_secs(2);
+const DEFAULT_DEBOUNCE: Duration = Duration::ZERO;
+const DEFAULT_STATE_DIR: &str = ".elevators";
+const DEFAULT_NETWORK_PORT: u16 = 20000;
+const DEFAULT_BROADCAST: IpAddr = IpAddr::V4(Ipv4Addr::BROADCAST);
+
+pub const USAGE: &str = "\
+Usage: elevators [OPTIONS]
+
+Options:
+    --config <FILE>          Load settings from a .toml or .json file
+    --id <ID>                Run only the elevator with the given id, and find the others
+                             over the network
+    --elevators <N>          Number of elevators [default: 2]
+    --floors <M>             Number of floors [default: 4]
+    --host <ADDR>            Address of the simulator(s) [default: 127.0.0.1]
+    --port <PORT>            Base port, elevator i connects to PORT + i [default: 10000]
+    --ports <P0,P1,...>      Explicit port for each elevator
+    --door-open-secs <S>     Time the door is kept open at a floor [default: 3]
+    --poll-interval-ms <MS>  Time between each check for new events [default: 10]
+    --obstruction-timeout-secs <S>
+                             Time the door can be kept from closing before the elevator
+                             gives its hall requests to the others [default: 10]
+    --travel-timeout-secs <S>
+                             Time the motor can run without reaching a floor before it
+                             is considered broken [default: 5]
+    --travel-time-secs <S>   Expected time to travel between two floors, used when
+                             assigning hall requests [default: 2]
+    --assigner <NAME>        Strategy for assigning hall requests: time-to-idle, nearest,
+                             round-robin or least-loaded [default: time-to-idle]
+    --debounce-ms <MS>       Time a button has to be held down or let go of before it
+                             counts, 0 turns debouncing off [default: 0]
+    --state-dir <DIR>        Directory where cab calls are stored [default: .elevators]
+    --network-port <PORT>    UDP port used to talk to the other elevators [default: 20000]
+    --broadcast <ADDR>       UDP broadcast address [default: 255.255.255.255]
+    -h, --help               Print this message
+
+Settings are applied in the order: defaults, config file, environment, command line.
+Every option can also be given as an environment variable, e.g. ELEVATORS_FLOORS=6.";
+
+/// Complete configuration of the program
+///
+/// Built in layers by Config::build(), where each layer overrides the previous one:
+/// defaults, config file, environment variables (ELEVATORS_*) and command-line arguments.
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub id: Option<usize>,
+    pub n_elevators: usize,
+    pub n_floors: usize,
+    pub addresses: Vec<SocketAddr>,
+    pub timing: Timing,
+    pub assigner: Strategy,
+    pub cost: CostWeights,
+    pub network: Network,
+    pub state_dir: PathBuf,
+}
+
+/// Timing parameters for the state machine of every elevator
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timing {
+    pub door_open_time: Duration,
+    pub poll_interval: Duration,
+    /// Time an obstruction can keep the door open before the elevator is unavailable
+    pub obstruction_timeout: Duration,
+    /// Time the motor can run without reaching a floor before it is considered broken
+    pub travel_timeout: Duration,
+    /// Expected time to travel between two floors, used to estimate when requests are served
+    pub travel_time: Duration,
+    /// Time a button has to keep its new level before a press or release counts
+    pub debounce: Duration,
+}
+
+/// Settings for talking to the other elevators, only used when running with --id
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Network {
+    pub port: u16,
+    pub broadcast: IpAddr,
+}
+
+/// Strategy used by the dispatcher to choose which elevator serves a hall request
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Strategy {
+    /// The elevator that would be done the soonest, redistributing every request as
+    /// the elevators move
+    #[default]
+    TimeToIdle,
+    /// The elevator with the lowest cost function
+    Nearest,
+    /// Each elevator in turn
+    RoundRobin,
+    /// The elevator with the fewest requests
+    LeastLoaded,
+}
+
+/// Weights used by TaskInfo::cost_function(), which breaks ties between elevators
+/// that would be done with a hall request equally soon
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CostWeights {
+    pub idle: usize,
+    pub moving: usize,
+    pub still: usize,
+    pub distance: usize,
+    pub requests: usize,
+    pub wrong_direction: usize,
+}
+
+impl Config {
+    /// Builds a config from command-line arguments (the first being the program name),
+    /// the process environment and the config file given by --config or ELEVATORS_CONFIG.
+    pub fn build(args: impl Iterator<Item = String>) -> Result<Config, String> {
+        Config::build_from(args, |key| env::var(key).ok())
+    }
+
+    /// Same as Config::build(), but with the environment given as a lookup function
+    pub fn build_from(
+        args: impl Iterator<Item = String>,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<Config, String> {
+        let cli = Overrides::from_args(args)?;
+        let env = Overrides::from_env(env)?;
+
+        let mut config = Config::default();
+        let mut addresses_given = false;
+        if let Some(path) = cli.config.as_ref().or(env.config.as_ref()) {
+            let file = FileConfig::load(path)?;
+            addresses_given = file.has_addresses();
+            file.apply(&mut config)
+                .map_err(|e| format!("in {}: {e}", path.display()))?;
+        }
+        for overrides in [env, cli] {
+            // Addresses from the config file are never replaced by generated ones
+            if addresses_given {
+                overrides.check_n_elevators(&config)?;
+                addresses_given = !overrides.replaces_addresses();
+            }
+            overrides.apply(&mut config)?;
+        }
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.n_elevators == 0 {
+            return Err("`elevators`: must be at least 1".into());
+        }
+        if self.n_floors < 2 {
+            return Err("`floors`: must be at least 2".into());
+        }
+        if self.addresses.len() != self.n_elevators {
+            return Err(format!(
+                "`addresses`: got {} addresses for {} elevators",
+                self.addresses.len(),
+                self.n_elevators
+            ));
+        }
+        if self.timing.door_open_time.is_zero() {
+            return Err("`door_open_secs`: must be greater than zero".into());
+        }
+        if self.timing.poll_interval.is_zero() {
+            return Err("`poll_interval_ms`: must be greater than zero".into());
+        }
+        if self.timing.obstruction_timeout.is_zero() {
+            return Err("`obstruction_timeout_secs`: must be greater than zero".into());
+        }
+        if self.timing.travel_timeout.is_zero() {
+            return Err("`travel_timeout_secs`: must be greater than zero".into());
+        }
+        if self.timing.travel_time.is_zero() {
+            return Err("`travel_time_secs`: must be greater than zero".into());
+        }
+        if let Some(id) = self.id {
+            if id >= self.n_elevators {
+                return Err(format!(
+                    "`id`: {id} is out of range for {} elevators",
+                    self.n_elevators
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Address of the simulator for the elevator with the given id
+    pub fn address(&self, id: usize) -> SocketAddr {
+        self.addresses[id]
+    }
+
+    /// Ids of the elevators that should be run by this process
+    pub fn local_ids(&self) -> Vec<usize> {
+        match self.id {
+            Some(id) => vec![id],
+            None => (0..self.n_elevators).collect(),
+        }
+    }
+
+    fn set_n_elevators(&mut self, n_elevators: usize) -> Result<(), String> {
+        self.n_elevators = n_elevators;
+        if n_elevators == self.addresses.len() {
+            return Ok(());
+        }
+        let first = self
+            .addresses
+            .first()
+            .copied()
+            .unwrap_or(SocketAddr::new(DEFAULT_HOST, DEFAULT_BASE_PORT));
+        self.addresses = contiguous(first.ip(), first.port(), n_elevators)?;
+        Ok(())
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            id: None,
+            n_elevators: DEFAULT_N_ELEVATORS,
+            n_floors: DEFAULT_N_FLOORS,
+            addresses: contiguous(DEFAULT_HOST, DEFAULT_BASE_PORT, DEFAULT_N_ELEVATORS).unwrap(),
+            timing: Timing::default(),
+            assigner: Strategy::default(),
+            cost: CostWeights::default(),
+            network: Network::default(),
+            state_dir: PathBuf::from(DEFAULT_STATE_DIR),
+        }
+    }
+}
+
+impl Default for Network {
+    fn default() -> Self {
+        Network {
+            port: DEFAULT_NETWORK_PORT,
+            broadcast: DEFAULT_BROADCAST,
+        }
+    }
+}
+
+impl Default for Timing {
+    fn default() -> Self {
+        Timing {
+            door_open_time: DEFAULT_DOOR_OPEN_TIME,
+            poll_interval: DEFAULT_POLL_INTERVAL,
+            obstruction_timeout: DEFAULT_OBSTRUCTION_TIMEOUT,
+            travel_timeout: DEFAULT_TRAVEL_TIMEOUT,
+            travel_time: DEFAULT_TRAVEL_TIME,
+            debounce: DEFAULT_DEBOUNCE,
+        }
+    }
+}
+
+impl Default for CostWeights {
+    fn default() -> Self {
+        CostWeights {
+            idle: 0,
+            moving: 1,
+            still: 3,
+            distance: 1,
+            requests: 2,
+            wrong_direction: 1,
+        }
+    }
+}
+
+impl Strategy {
+    pub fn iterator() -> impl Iterator<Item = Strategy> {
+        [
+            Strategy::TimeToIdle,
+            Strategy::Nearest,
+            Strategy::RoundRobin,
+            Strategy::LeastLoaded,
+        ]
+        .iter()
+        .copied()
+    }
+}
+
+impl FromStr for Strategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Strategy, String> {
+        match s {
+            "time-to-idle" => Ok(Strategy::TimeToIdle),
+            "nearest" => Ok(Strategy::Nearest),
+            "round-robin" => Ok(Strategy::RoundRobin),
+            "least-loaded" => Ok(Strategy::LeastLoaded),
+            _ => Err(format!("unknown assigner '{s}'")),
+        }
+    }
+}
+
+impl std::fmt::Display for Strategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let name = match self {
+            Strategy::TimeToIdle => "time-to-idle",
+            Strategy::Nearest => "nearest",
+            Strategy::RoundRobin => "round-robin",
+            Strategy::LeastLoaded => "least-loaded",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// Addresses of `n` elevators on the same host, on consecutive ports from `base_port`
+fn contiguous(host: IpAddr, base_port: u16, n: usize) -> Result<Vec<SocketAddr>, String> {
+    (0..n)
+        .map(|i| {
+            u16::try_from(i)
+                .ok()
+                .and_then(|i| base_port.checked_add(i))
+                .map(|port| SocketAddr::new(host, port))
+                .ok_or(format!(
+                    "`port`: {n} elevators from port {base_port} go past port 65535"
+                ))
+        })
+        .collect()
+}
+
+fn secs_to_duration(key: &str, secs: f64) -> Result<Duration, String> {
+    Duration::try_from_secs_f64(secs).map_err(|_| format!("`{key}`: invalid duration {secs}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::io::Write;
+
+    use super::*;
+
+    fn build(args: &str, env: &[(&str, &str)]) -> Result<Config, String> {
+        let args = std::iter::once("elevators").chain(args.split_whitespace());
+        let env: HashMap<String, String> = env
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        Config::build_from(args.map(String::from), |key| env.get(key).cloned())
+    }
+
+    fn config_file(name: &str, contents: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!("elevators-{}-{name}", std::process::id()));
+        let mut file = std::fs::File::create(&path).unwrap();
+        file.write_all(contents.as_bytes()).unwrap();
+        path
+    }
+
+    #[test]
+    fn defaults() {
+        let config = build("", &[]).unwrap();
+        assert_eq!(config.id, None);
+        assert_eq!(config.n_elevators, 2);
+        assert_eq!(config.n_floors, 4);
+        assert_eq!(config.address(1), SocketAddr::from(([127, 0, 0, 1], 10001)));
+        assert_eq!(config.timing, Timing::default());
+    }
+
+    #[test]
+    fn base_port_and_elevators() {
+        let config = build("--port 15657 --elevators 3 --floors 6 --id 2", &[]).unwrap();
+        assert_eq!(config.address(2), SocketAddr::from(([127, 0, 0, 1], 15659)));
+        assert_eq!(config.n_floors, 6);
+        assert_eq!(config.local_ids(), vec![2]);
+    }
+
+    #[test]
+    fn explicit_ports() {
+        let config = build("--host 10.0.0.5 --ports 15657,15700", &[]).unwrap();
+        assert_eq!(config.n_elevators, 2);
+        assert_eq!(config.address(1), SocketAddr::from(([10, 0, 0, 5], 15700)));
+
+        assert!(build("--ports 15657,15700 --elevators 3", &[]).is_err());
+        assert!(build("--ports 15657 --port 15657", &[]).is_err());
+    }
+
+    #[test]
+    fn port_range_overflow() {
+        let config = build("--port 65534 --elevators 2", &[]).unwrap();
+        assert_eq!(config.address(1).port(), 65535);
+
+        assert!(build("--port 65535 --elevators 2", &[]).is_err());
+        assert!(build("--port 65000 --elevators 70000", &[]).is_err());
+        assert!(build("--elevators 3", &[("ELEVATORS_PORT", "65534")]).is_err());
+    }
+
+    #[test]
+    fn invalid_arguments() {
+        assert!(build("--id 2", &[]).is_err());
+        assert!(build("--floors", &[]).is_err());
+        assert!(build("--floors four", &[]).is_err());
+        assert!(build("--verbose", &[]).is_err());
+        assert!(build("--door-open-secs -1", &[]).is_err());
+        assert!(build("--assigner fastest", &[]).is_err());
+    }
+
+    #[test]
+    fn layered_overrides() {
+        let path = config_file(
+            "layers.toml",
+            r#"
+            floors = 6
+            addresses = ["10.0.0.1:15657", "10.0.0.2:15657", "10.0.0.3:15657"]
+            door_open_secs = 2.5
+            obstruction_timeout_secs = 20
+            travel_timeout_secs = 8
+            travel_time_secs = 3.5
+            debounce_ms = 30
+            assigner = "least-loaded"
+            broadcast = "10.0.0.255"
+
+            [cost]
+            distance = 4
+            "#,
+        );
+        let path = path.to_str().unwrap();
+
+        let config = build(&format!("--config {path}"), &[]).unwrap();
+        assert_eq!(config.n_floors, 6);
+        assert_eq!(config.n_elevators, 3);
+        assert_eq!(config.address(2), SocketAddr::from(([10, 0, 0, 3], 15657)));
+        assert_eq!(config.timing.door_open_time, Duration::from_millis(2500));
+        assert_eq!(config.timing.obstruction_timeout, Duration::from_secs(20));
+        assert_eq!(config.timing.travel_timeout, Duration::from_secs(8));
+        assert_eq!(config.timing.travel_time, Duration::from_millis(3500));
+        assert_eq!(config.timing.debounce, Duration::from_millis(30));
+        assert_eq!(config.assigner, Strategy::LeastLoaded);
+        assert_eq!(config.network.broadcast, IpAddr::from([10, 0, 0, 255]));
+        assert_eq!(config.cost.distance, 4);
+        assert_eq!(config.cost.requests, CostWeights::default().requests);
+
+        let env = [("ELEVATORS_CONFIG", path), ("ELEVATORS_FLOORS", "5")];
+        let config = build("", &env).unwrap();
+        assert_eq!(config.n_floors, 5);
+        assert_eq!(config.n_elevators, 3);
+
+        let config = build("--floors 8", &env).unwrap();
+        assert_eq!(config.n_floors, 8);
+
+        // The addresses are kept, unless they are replaced by ports
+        let config = build("--elevators 3", &env).unwrap();
+        assert_eq!(config.address(1), SocketAddr::from(([10, 0, 0, 2], 15657)));
+        let err = build("--elevators 2", &env).unwrap_err();
+        assert!(err.contains("`addresses`"), "{err}");
+        let config = build("--elevators 2 --port 15657", &env).unwrap();
+        assert_eq!(config.address(1), SocketAddr::from(([10, 0, 0, 1], 15658)));
+    }
+
+    #[test]
+    fn errors_name_the_bad_key() {
+        let path = config_file("typo.json", r#"{ "flors": 4 }"#);
+        let err = build(&format!("--config {}", path.display()), &[]).unwrap_err();
+        assert!(err.contains("flors"), "{err}");
+
+        let path = config_file("range.toml", "floors = 1");
+        let err = build(&format!("--config {}", path.display()), &[]).unwrap_err();
+        assert!(err.contains("`floors`"), "{err}");
+
+        let err = build("", &[("ELEVATORS_POLL_INTERVAL_MS", "often")]).unwrap_err();
+        assert!(err.contains("ELEVATORS_POLL_INTERVAL_MS"), "{err}");
+    }
+}
@@ -0,0 +1,94 @@
+use std::fs;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use super::{secs_to_duration, Config, CostWeights, Strategy};
+
+/// Settings that can be given in a config file, all of them optional
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FileConfig {
+    floors: Option<usize>,
+    addresses: Option<Vec<SocketAddr>>,
+    door_open_secs: Option<f64>,
+    poll_interval_ms: Option<u64>,
+    obstruction_timeout_secs: Option<f64>,
+    travel_timeout_secs: Option<f64>,
+    travel_time_secs: Option<f64>,
+    debounce_ms: Option<u64>,
+    assigner: Option<Strategy>,
+    network_port: Option<u16>,
+    broadcast: Option<IpAddr>,
+    state_dir: Option<PathBuf>,
+    cost: Option<CostWeights>,
+}
+
+impl FileConfig {
+    /// Reads a config file, the format is decided by the extension (.toml or .json)
+    pub fn load(path: &Path) -> Result<FileConfig, String> {
+        let name = path.display();
+        let contents =
+            fs::read_to_string(path).map_err(|e| format!("could not read {name}: {e}"))?;
+
+        let file: FileConfig = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => toml::from_str(&contents).map_err(|e| format!("in {name}: {e}"))?,
+            Some("json") => {
+                serde_json::from_str(&contents).map_err(|e| format!("in {name}: {e}"))?
+            }
+            _ => return Err(format!("{name}: config file must end in .toml or .json")),
+        };
+
+        Ok(file)
+    }
+
+    pub fn has_addresses(&self) -> bool {
+        self.addresses.is_some()
+    }
+
+    pub fn apply(self, config: &mut Config) -> Result<(), String> {
+        if let Some(floors) = self.floors {
+            config.n_floors = floors;
+        }
+        if let Some(addresses) = self.addresses {
+            config.n_elevators = addresses.len();
+            config.addresses = addresses;
+        }
+        if let Some(secs) = self.door_open_secs {
+            config.timing.door_open_time = secs_to_duration("door_open_secs", secs)?;
+        }
+        if let Some(ms) = self.poll_interval_ms {
+            config.timing.poll_interval = Duration::from_millis(ms);
+        }
+        if let Some(secs) = self.obstruction_timeout_secs {
+            config.timing.obstruction_timeout = secs_to_duration("obstruction_timeout_secs", secs)?;
+        }
+        if let Some(secs) = self.travel_timeout_secs {
+            config.timing.travel_timeout = secs_to_duration("travel_timeout_secs", secs)?;
+        }
+        if let Some(secs) = self.travel_time_secs {
+            config.timing.travel_time = secs_to_duration("travel_time_secs", secs)?;
+        }
+        if let Some(ms) = self.debounce_ms {
+            config.timing.debounce = Duration::from_millis(ms);
+        }
+        if let Some(assigner) = self.assigner {
+            config.assigner = assigner;
+        }
+        if let Some(port) = self.network_port {
+            config.network.port = port;
+        }
+        if let Some(broadcast) = self.broadcast {
+            config.network.broadcast = broadcast;
+        }
+        if let Some(state_dir) = self.state_dir {
+            config.state_dir = state_dir;
+        }
+        if let Some(cost) = self.cost {
+            config.cost = cost;
+        }
+        Ok(())
+    }
+}
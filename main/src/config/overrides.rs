@@ -0,0 +1,260 @@
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+use super::{contiguous, secs_to_duration, Config, Strategy, DEFAULT_HOST};
+
+/// Settings given as command-line arguments or environment variables
+#[derive(Debug, Default)]
+pub struct Overrides {
+    pub config: Option<PathBuf>,
+    id: Option<usize>,
+    n_elevators: Option<usize>,
+    n_floors: Option<usize>,
+    host: Option<IpAddr>,
+    base_port: Option<u16>,
+    ports: Option<Vec<u16>>,
+    door_open_time: Option<Duration>,
+    poll_interval: Option<Duration>,
+    obstruction_timeout: Option<Duration>,
+    travel_timeout: Option<Duration>,
+    travel_time: Option<Duration>,
+    debounce: Option<Duration>,
+    assigner: Option<Strategy>,
+    network_port: Option<u16>,
+    broadcast: Option<IpAddr>,
+    state_dir: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Key {
+    Config,
+    Id,
+    Elevators,
+    Floors,
+    Host,
+    Port,
+    Ports,
+    DoorOpenSecs,
+    PollIntervalMs,
+    ObstructionTimeoutSecs,
+    TravelTimeoutSecs,
+    TravelTimeSecs,
+    DebounceMs,
+    Assigner,
+    NetworkPort,
+    Broadcast,
+    StateDir,
+}
+
+/// Every setting as (key, command-line argument, environment variable)
+const KEYS: [(Key, &str, &str); 17] = [
+    (Key::Config, "--config", "ELEVATORS_CONFIG"),
+    (Key::Id, "--id", "ELEVATORS_ID"),
+    (Key::Elevators, "--elevators", "ELEVATORS_ELEVATORS"),
+    (Key::Floors, "--floors", "ELEVATORS_FLOORS"),
+    (Key::Host, "--host", "ELEVATORS_HOST"),
+    (Key::Port, "--port", "ELEVATORS_PORT"),
+    (Key::Ports, "--ports", "ELEVATORS_PORTS"),
+    (
+        Key::DoorOpenSecs,
+        "--door-open-secs",
+        "ELEVATORS_DOOR_OPEN_SECS",
+    ),
+    (
+        Key::PollIntervalMs,
+        "--poll-interval-ms",
+        "ELEVATORS_POLL_INTERVAL_MS",
+    ),
+    (
+        Key::ObstructionTimeoutSecs,
+        "--obstruction-timeout-secs",
+        "ELEVATORS_OBSTRUCTION_TIMEOUT_SECS",
+    ),
+    (
+        Key::TravelTimeoutSecs,
+        "--travel-timeout-secs",
+        "ELEVATORS_TRAVEL_TIMEOUT_SECS",
+    ),
+    (
+        Key::TravelTimeSecs,
+        "--travel-time-secs",
+        "ELEVATORS_TRAVEL_TIME_SECS",
+    ),
+    (Key::DebounceMs, "--debounce-ms", "ELEVATORS_DEBOUNCE_MS"),
+    (Key::Assigner, "--assigner", "ELEVATORS_ASSIGNER"),
+    (Key::NetworkPort, "--network-port", "ELEVATORS_NETWORK_PORT"),
+    (Key::Broadcast, "--broadcast", "ELEVATORS_BROADCAST"),
+    (Key::StateDir, "--state-dir", "ELEVATORS_STATE_DIR"),
+];
+
+impl Overrides {
+    pub fn from_args(mut args: impl Iterator<Item = String>) -> Result<Overrides, String> {
+        args.next();
+
+        let mut overrides = Overrides::default();
+        while let Some(arg) = args.next() {
+            let (key, ..) = KEYS
+                .iter()
+                .find(|(_, flag, _)| *flag == arg)
+                .ok_or(format!("unknown argument '{arg}'"))?;
+            let value = args
+                .next()
+                .ok_or(format!("missing value for argument '{arg}'"))?;
+            overrides.set(*key, &arg, &value)?;
+        }
+
+        overrides.check_exclusive("'--port' and '--ports'")?;
+        Ok(overrides)
+    }
+
+    pub fn from_env(env: impl Fn(&str) -> Option<String>) -> Result<Overrides, String> {
+        let mut overrides = Overrides::default();
+        for (key, _, var) in KEYS {
+            if let Some(value) = env(var) {
+                overrides.set(key, var, &value)?;
+            }
+        }
+
+        overrides.check_exclusive("ELEVATORS_PORT and ELEVATORS_PORTS")?;
+        Ok(overrides)
+    }
+
+    fn set(&mut self, key: Key, name: &str, value: &str) -> Result<(), String> {
+        match key {
+            Key::Config => self.config = Some(PathBuf::from(value)),
+            Key::Id => self.id = Some(parse(name, value)?),
+            Key::Elevators => self.n_elevators = Some(parse(name, value)?),
+            Key::Floors => self.n_floors = Some(parse(name, value)?),
+            Key::Host => self.host = Some(parse(name, value)?),
+            Key::Port => self.base_port = Some(parse(name, value)?),
+            Key::Ports => {
+                let ports = value
+                    .split(',')
+                    .map(|port| parse(name, port.trim()))
+                    .collect::<Result<_, _>>()?;
+                self.ports = Some(ports);
+            }
+            Key::DoorOpenSecs => {
+                self.door_open_time = Some(secs_to_duration(name, parse(name, value)?)?);
+            }
+            Key::PollIntervalMs => {
+                self.poll_interval = Some(Duration::from_millis(parse(name, value)?));
+            }
+            Key::ObstructionTimeoutSecs => {
+                self.obstruction_timeout = Some(secs_to_duration(name, parse(name, value)?)?);
+            }
+            Key::TravelTimeoutSecs => {
+                self.travel_timeout = Some(secs_to_duration(name, parse(name, value)?)?);
+            }
+            Key::TravelTimeSecs => {
+                self.travel_time = Some(secs_to_duration(name, parse(name, value)?)?);
+            }
+            Key::DebounceMs => {
+                self.debounce = Some(Duration::from_millis(parse(name, value)?));
+            }
+            Key::Assigner => self.assigner = Some(parse(name, value)?),
+            Key::NetworkPort => self.network_port = Some(parse(name, value)?),
+            Key::Broadcast => self.broadcast = Some(parse(name, value)?),
+            Key::StateDir => self.state_dir = Some(PathBuf::from(value)),
+        }
+        Ok(())
+    }
+
+    fn check_exclusive(&self, keys: &str) -> Result<(), String> {
+        if self.base_port.is_some() && self.ports.is_some() {
+            return Err(format!("{keys} are mutually exclusive"));
+        }
+        Ok(())
+    }
+
+    /// Whether the addresses are replaced by ones built from the ports
+    pub fn replaces_addresses(&self) -> bool {
+        self.ports.is_some() || self.base_port.is_some()
+    }
+
+    /// Checks that the number of elevators matches the addresses that are kept
+    pub fn check_n_elevators(&self, config: &Config) -> Result<(), String> {
+        match self.n_elevators {
+            Some(n) if !self.replaces_addresses() && n != config.addresses.len() => Err(format!(
+                "`elevators`: {n} elevators do not match the {} `addresses` in the config file",
+                config.addresses.len()
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn apply(self, config: &mut Config) -> Result<(), String> {
+        if let Some(id) = self.id {
+            config.id = Some(id);
+        }
+        if let Some(n_floors) = self.n_floors {
+            config.n_floors = n_floors;
+        }
+
+        let explicit_ports = self.ports.is_some();
+        let host = self.host.unwrap_or_else(|| {
+            let first = config.addresses.first();
+            first.map_or(DEFAULT_HOST, |addr| addr.ip())
+        });
+        if let Some(ports) = self.ports {
+            config.addresses = ports
+                .into_iter()
+                .map(|port| SocketAddr::new(host, port))
+                .collect();
+        } else if let Some(base_port) = self.base_port {
+            let n = self.n_elevators.unwrap_or(config.n_elevators);
+            config.addresses = contiguous(host, base_port, n)?;
+        } else if let Some(host) = self.host {
+            config
+                .addresses
+                .iter_mut()
+                .for_each(|addr| addr.set_ip(host));
+        }
+
+        match self.n_elevators {
+            Some(n_elevators) if !explicit_ports => config.set_n_elevators(n_elevators)?,
+            Some(n_elevators) => config.n_elevators = n_elevators,
+            None => config.n_elevators = config.addresses.len(),
+        }
+
+        if let Some(door_open_time) = self.door_open_time {
+            config.timing.door_open_time = door_open_time;
+        }
+        if let Some(poll_interval) = self.poll_interval {
+            config.timing.poll_interval = poll_interval;
+        }
+        if let Some(obstruction_timeout) = self.obstruction_timeout {
+            config.timing.obstruction_timeout = obstruction_timeout;
+        }
+        if let Some(travel_timeout) = self.travel_timeout {
+            config.timing.travel_timeout = travel_timeout;
+        }
+        if let Some(travel_time) = self.travel_time {
+            config.timing.travel_time = travel_time;
+        }
+        if let Some(debounce) = self.debounce {
+            config.timing.debounce = debounce;
+        }
+        if let Some(assigner) = self.assigner {
+            config.assigner = assigner;
+        }
+        if let Some(port) = self.network_port {
+            config.network.port = port;
+        }
+        if let Some(broadcast) = self.broadcast {
+            config.network.broadcast = broadcast;
+        }
+        if let Some(state_dir) = self.state_dir {
+            config.state_dir = state_dir;
+        }
+        Ok(())
+    }
+}
+
+fn parse<T: FromStr>(key: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value '{value}' for {key}"))
+}
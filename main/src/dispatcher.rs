@@ -0,0 +1,804 @@
+use std::collections::{HashMap, HashSet};
+use std::future::pending;
+use std::time::Duration;
+use tokio::sync::mpsc::{Receiver, Sender};
+use tokio::time::{interval, Instant};
+
+use interface::types::{Direction, Floor};
+
+use crate::network::types::{NetworkEvent, Packet};
+use crate::types::{HallRequests, Health, Message, OrderState, TaskInfo};
+
+pub mod assigner;
+
+use self::assigner::{Assigner, Call, Candidate};
+
+const SYNC_INTERVAL: Duration = Duration::from_millis(100);
+/// Backed up cab calls are only restored this soon after startup, so that calls
+/// served since the startup are not brought back by a late backup
+const RESTORE_WINDOW: Duration = Duration::from_secs(3);
+/// Time a local elevator has to accept its hall requests, before they are given to others
+const ACK_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Routes messages between the elevators, and assigns hall requests to them
+///
+/// The tasks run by this process are listed in `local_ids`. When running over
+/// the network, the remaining tasks forward their messages to the other processes,
+/// and the hall requests are kept in sync with the other nodes.
+///
+/// Every hall request goes through assign -> accept -> serve: the owner in the hall
+/// request table is sent its requests with AssignedHallRequests, answers with
+/// HallRequestsAccepted, and turns the hall light off once it has served a request.
+/// An elevator that does not accept its requests within ACK_TIMEOUT is marked as
+/// Health::Unresponsive, so every lit hall light always has an owner that responds.
+pub struct Dispatcher {
+    tasks: Vec<TaskInfo>,
+    local_ids: Vec<usize>,
+    assigner: Box<dyn Assigner>,
+    network: Option<Sender<Packet>>,
+    node: usize,
+    hall_requests: HallRequests,
+    /// Hall lights currently turned on at the local elevators
+    lights: HashSet<(Floor, Direction)>,
+    /// Hall requests last given to each local elevator, with the version of each order
+    delivered: HashMap<usize, Vec<(Floor, Direction, u64)>>,
+    /// Latest assignment sent to each local elevator, the only one it can accept
+    latest_assignment: HashMap<usize, u64>,
+    /// Local elevators that have not accepted their latest assignment yet, and when
+    /// the oldest assignment still waiting for an answer was sent
+    unaccepted: HashMap<usize, Instant>,
+    next_assignment: u64,
+    /// Latest cab calls of every elevator, both local and remote, along with the
+    /// session of the state machine that reported them
+    cab_calls: HashMap<usize, (u64, Vec<bool>)>,
+    started: Instant,
+}
+
+impl Dispatcher {
+    pub fn new(
+        mut tasks: Vec<TaskInfo>,
+        local_ids: Vec<usize>,
+        assigner: Box<dyn Assigner>,
+        network: Option<(usize, Sender<Packet>)>,
+    ) -> Dispatcher {
+        tasks.sort_by_key(|task| task.id);
+        let (node, network) = match network {
+            Some((id, network)) => (id, Some(network)),
+            None => (0, None),
+        };
+
+        Dispatcher {
+            tasks,
+            local_ids,
+            assigner,
+            network,
+            node,
+            hall_requests: HallRequests::new(Floor::get_n_floors()),
+            lights: HashSet::new(),
+            delivered: HashMap::new(),
+            latest_assignment: HashMap::new(),
+            unaccepted: HashMap::new(),
+            next_assignment: 0,
+            cab_calls: HashMap::new(),
+            started: Instant::now(),
+        }
+    }
+
+    pub async fn run(
+        mut self,
+        mut rx: Receiver<Message>,
+        mut rx_network: Option<Receiver<NetworkEvent>>,
+    ) {
+        let mut sync = interval(SYNC_INTERVAL);
+
+        loop {
+            tokio::select! {
+                msg = rx.recv() => match msg {
+                    Some(msg) => self.handle_local(msg).await,
+                    None => return,
+                },
+                Some(event) = recv_optional(&mut rx_network) => {
+                    self.handle_network(event).await;
+                }
+                _ = sync.tick() => {
+                    self.check_accepted().await;
+                    self.update_hall_requests(true).await;
+                    self.broadcast_cab_calls().await;
+                }
+            }
+        }
+    }
+
+    async fn handle_local(&mut self, msg: Message) {
+        match msg {
+            Message::Request { floor, direction } => {
+                let changed = self.hall_requests.press(floor, direction, self.node);
+                self.update_hall_requests(changed).await;
+            }
+            Message::HallButtonLight {
+                floor,
+                direction,
+                on: false,
+            } => {
+                let changed = self.hall_requests.serve(floor, direction, self.node);
+                self.update_hall_requests(changed).await;
+            }
+            Message::HallButtonLight { on: true, .. } => {
+                eprintln!("Hall lights are controlled by the dispatcher, ignoring {msg:?}");
+            }
+            Message::ElevatorInfo { .. } => {
+                let changed = self.update_info(&msg);
+                self.broadcast(msg).await;
+                if changed {
+                    self.update_hall_requests(false).await;
+                }
+            }
+            Message::CabCalls {
+                task_id,
+                session,
+                ref calls,
+            } => {
+                self.cab_calls.insert(task_id, (session, calls.clone()));
+                self.broadcast(msg).await;
+            }
+            Message::RestoreCabCalls { .. } | Message::AssignedHallRequests { .. } => {
+                eprintln!("Only the dispatcher sends {msg:?}, ignoring it");
+            }
+            Message::HallRequestsAccepted {
+                task_id,
+                assignment,
+            } => {
+                self.accepted(task_id, assignment).await;
+            }
+            Message::Health { task_id, health } => {
+                println!("Elevator {task_id} is now {health}");
+                let was_available = self.is_available(task_id);
+                self.set_health(task_id, health);
+                self.broadcast(msg).await;
+                if health.is_available() && !was_available {
+                    self.resend_hall_requests(task_id).await;
+                }
+                // Hall requests of an unavailable elevator are given to the others right away
+                self.update_hall_requests(true).await;
+            }
+            Message::Shutdown => {
+                eprint!("Received shutdown message from task");
+            }
+        }
+    }
+
+    async fn handle_network(&mut self, event: NetworkEvent) {
+        match event {
+            NetworkEvent::Message { msg, .. } => match msg {
+                // Hall requests of the other nodes arrive through their replicated tables
+                Message::Request { .. } => {}
+                Message::HallButtonLight { .. } => {
+                    self.send_to_local(msg).await;
+                }
+                Message::ElevatorInfo { .. } => {
+                    if self.update_info(&msg) {
+                        self.update_hall_requests(false).await;
+                    }
+                }
+                Message::CabCalls {
+                    task_id,
+                    session,
+                    calls,
+                } => {
+                    if !self.local_ids.contains(&task_id) {
+                        self.back_up_cab_calls(task_id, session, calls).await;
+                    }
+                }
+                Message::RestoreCabCalls { task_id, .. } => {
+                    if self.started.elapsed() < RESTORE_WINDOW {
+                        self.send_to(task_id, msg).await;
+                    }
+                }
+                Message::Health { task_id, health } => {
+                    if !self.local_ids.contains(&task_id) {
+                        self.set_health(task_id, health);
+                        self.update_hall_requests(false).await;
+                    }
+                }
+                Message::AssignedHallRequests { .. }
+                | Message::HallRequestsAccepted { .. }
+                | Message::Shutdown => {}
+            },
+            NetworkEvent::HallRequests { requests, .. } => {
+                let changed = self.hall_requests.merge(&requests, self.node);
+                self.update_hall_requests(changed).await;
+            }
+            NetworkEvent::PeerJoined(id) => {
+                self.set_connected(id, true);
+                self.broadcast_availability().await;
+                self.update_hall_requests(true).await;
+            }
+            NetworkEvent::PeerLeft(id) => {
+                self.set_connected(id, false);
+                self.update_hall_requests(false).await;
+            }
+        }
+    }
+
+    /// Moves the hall requests forward in their lifecycle, and lets the local
+    /// elevators know about any changes. The table is shared with the other
+    /// nodes if anything changed, or if `broadcast` is set.
+    async fn update_hall_requests(&mut self, broadcast: bool) {
+        let nodes = self.connected_nodes();
+        let mut changed = self.hall_requests.confirm(&nodes, self.node);
+
+        // Only the connected node with the lowest id assigns requests, to avoid conflicts
+        if nodes.iter().min() == Some(&self.node) {
+            changed |= self.assign_hall_requests();
+        }
+
+        self.deliver_hall_requests().await;
+
+        if let (true, Some(network)) = (broadcast || changed, &self.network) {
+            let packet = Packet::hall_requests(self.node, self.hall_requests.clone());
+            let _ = network.send(packet).await;
+        }
+    }
+
+    /// Distributes every active hall request among the available elevators, see
+    /// Assigner::distribute()
+    ///
+    /// Runs whenever an elevator changes state, joins or leaves, so that the assigner
+    /// can move requests to whichever elevator suits them better. A request is never
+    /// dropped while it changes hands: it stays in the shared table until it is
+    /// served, the old owner only lets go of it once the table names the new one,
+    /// and it is moved again if the new owner becomes unavailable.
+    fn assign_hall_requests(&mut self) -> bool {
+        let calls: Vec<Call> = self
+            .hall_requests
+            .iter()
+            .filter(|(_, _, order)| order.is_active())
+            .map(|(floor, direction, order)| Call {
+                floor,
+                direction,
+                owner: match order.state {
+                    OrderState::Assigned { owner } => Some(owner),
+                    _ => None,
+                },
+            })
+            .collect();
+
+        let elevators: Vec<_> = self
+            .tasks
+            .iter()
+            .filter(|task| task.is_available())
+            .map(|task| Candidate {
+                task,
+                requests: task.cab_requests(),
+            })
+            .collect();
+
+        // Without available elevators, new requests go to a local elevator so that
+        // they are still served when this process is disconnected
+        let owners = if elevators.is_empty() {
+            let local = self.local_tasks().next().unwrap().id;
+            calls
+                .iter()
+                .map(|call| call.owner.unwrap_or(local))
+                .collect()
+        } else {
+            self.assigner.distribute(&elevators, &calls)
+        };
+
+        let mut changed = false;
+        for (call, owner) in calls.into_iter().zip(owners) {
+            let Call {
+                floor, direction, ..
+            } = call;
+            if call.owner != Some(owner) {
+                println!("Assigning hall request {direction} at floor {floor} to elevator {owner}");
+                self.hall_requests
+                    .assign(floor, direction, owner, self.node);
+                changed = true;
+            }
+        }
+
+        changed
+    }
+
+    /// Gives the local elevators their assigned requests, and updates the hall lights
+    ///
+    /// An elevator is sent its whole set of hall requests whenever it changes.
+    async fn deliver_hall_requests(&mut self) {
+        let mut messages = Vec::new();
+        let mut assigned: HashMap<usize, Vec<_>> =
+            self.local_ids.iter().map(|&id| (id, Vec::new())).collect();
+        for (floor, direction, order) in self.hall_requests.iter() {
+            if let OrderState::Assigned { owner } = order.state {
+                if let Some(requests) = assigned.get_mut(&owner) {
+                    requests.push((floor, direction, order.version));
+                }
+            }
+
+            let on = order.is_active();
+            if on != self.lights.contains(&(floor, direction)) {
+                if on {
+                    self.lights.insert((floor, direction));
+                } else {
+                    self.lights.remove(&(floor, direction));
+                }
+                let msg = Message::HallButtonLight {
+                    floor,
+                    direction,
+                    on,
+                };
+                messages.extend(self.local_ids.iter().map(|&id| (id, msg.clone())));
+            }
+        }
+
+        for (id, requests) in assigned {
+            if self.delivered.get(&id) != Some(&requests) {
+                messages.push((id, self.assignment(id, &requests)));
+                self.delivered.insert(id, requests);
+            }
+        }
+
+        for (id, msg) in messages {
+            self.send_to(id, msg).await;
+        }
+    }
+
+    /// Gives a restarted or reconnected local elevator its assigned requests and
+    /// every hall light again, since it may have missed them while it was unavailable
+    async fn resend_hall_requests(&mut self, id: usize) {
+        let mut messages = Vec::new();
+        let mut requests = Vec::new();
+        for (floor, direction, order) in self.hall_requests.iter() {
+            if order.state == (OrderState::Assigned { owner: id }) {
+                requests.push((floor, direction, order.version));
+            }
+            messages.push(Message::HallButtonLight {
+                floor,
+                direction,
+                on: order.is_active(),
+            });
+        }
+        // Anything sent before is likely lost, so the elevator gets a full ACK_TIMEOUT
+        self.unaccepted.remove(&id);
+        messages.push(self.assignment(id, &requests));
+        self.delivered.insert(id, requests);
+
+        for msg in messages {
+            self.send_to(id, msg).await;
+        }
+    }
+
+    /// Numbers a set of hall requests for a local elevator, which it has to accept
+    /// within ACK_TIMEOUT
+    fn assignment(&mut self, id: usize, requests: &[(Floor, Direction, u64)]) -> Message {
+        self.next_assignment += 1;
+        let assignment = self.next_assignment;
+        self.latest_assignment.insert(id, assignment);
+        self.unaccepted.entry(id).or_insert_with(Instant::now);
+
+        let requests = requests
+            .iter()
+            .map(|&(floor, direction, _)| (floor, direction))
+            .collect();
+        Message::AssignedHallRequests {
+            assignment,
+            requests,
+        }
+    }
+
+    /// Handles a local elevator accepting an assignment, which also makes an
+    /// unresponsive elevator available again
+    ///
+    /// Only the latest assignment counts. An elevator that accepts an older one has
+    /// not seen its current requests yet, so it is still given until ACK_TIMEOUT
+    /// after they were first sent.
+    async fn accepted(&mut self, id: usize, assignment: u64) {
+        if self.latest_assignment.get(&id) != Some(&assignment) {
+            return;
+        }
+        self.unaccepted.remove(&id);
+
+        let unresponsive = self
+            .tasks
+            .iter()
+            .any(|task| task.id == id && task.health == Health::Unresponsive);
+        if unresponsive {
+            self.report_health(id, Health::Healthy).await;
+            self.resend_hall_requests(id).await;
+            self.update_hall_requests(true).await;
+        }
+    }
+
+    /// Marks the local elevators that have not accepted an assignment in time as
+    /// unresponsive, so that their hall requests are given to the others
+    async fn check_accepted(&mut self) {
+        let late: Vec<usize> = self
+            .unaccepted
+            .iter()
+            .filter(|(_, sent)| sent.elapsed() >= ACK_TIMEOUT)
+            .map(|(&id, _)| id)
+            .collect();
+
+        for id in late {
+            self.unaccepted.remove(&id);
+            if self.is_available(id) {
+                self.report_health(id, Health::Unresponsive).await;
+            }
+        }
+    }
+
+    /// Changes the health of a local elevator on its behalf, and lets the other nodes know
+    async fn report_health(&mut self, id: usize, health: Health) {
+        println!("Elevator {id} is now {health}");
+        self.set_health(id, health);
+        let msg = Message::Health {
+            task_id: id,
+            health,
+        };
+        self.broadcast(msg).await;
+    }
+
+    /// Keeps the cab calls of a remote elevator, in case it loses them
+    ///
+    /// A restarted elevator first reports the calls it found on disk, which are none
+    /// after a power loss. When a new session is seen, the backup is sent back to the
+    /// elevator and kept along with the reported calls, instead of being replaced.
+    async fn back_up_cab_calls(&mut self, id: usize, session: u64, calls: Vec<bool>) {
+        let backup = match self.cab_calls.remove(&id) {
+            Some((known, backup)) if known != session && backup.len() == calls.len() => backup,
+            _ => {
+                self.cab_calls.insert(id, (session, calls));
+                return;
+            }
+        };
+
+        self.restore_cab_calls(id, &backup).await;
+        let calls = calls.iter().zip(&backup).map(|(&a, &b)| a || b).collect();
+        self.cab_calls.insert(id, (session, calls));
+    }
+
+    /// Sends backed up cab calls back to a remote elevator that has restarted
+    async fn restore_cab_calls(&mut self, id: usize, calls: &[bool]) {
+        if !calls.contains(&true) {
+            return;
+        }
+
+        let msg = Message::RestoreCabCalls {
+            task_id: id,
+            calls: calls.to_vec(),
+        };
+        if let Some(network) = &self.network {
+            let _ = network
+                .send(Packet::message(self.node, Some(id), msg))
+                .await;
+        }
+    }
+
+    /// Repeats the cab calls of the local elevators, in case any packets were lost
+    async fn broadcast_cab_calls(&mut self) {
+        for id in self.local_ids.clone() {
+            if let Some((session, calls)) = self.cab_calls.get(&id) {
+                let msg = Message::CabCalls {
+                    task_id: id,
+                    session: *session,
+                    calls: calls.clone(),
+                };
+                self.broadcast(msg).await;
+            }
+        }
+    }
+
+    /// Lets a peer that just joined know which of the local elevators are running
+    async fn broadcast_availability(&mut self) {
+        let messages: Vec<_> = self
+            .local_tasks()
+            .map(|task| Message::Health {
+                task_id: task.id,
+                health: task.health,
+            })
+            .collect();
+        for msg in messages {
+            self.broadcast(msg).await;
+        }
+    }
+
+    async fn broadcast(&self, msg: Message) {
+        if let Some(network) = &self.network {
+            let _ = network.send(Packet::message(self.node, None, msg)).await;
+        }
+    }
+
+    /// Stores the reported state of an elevator, returns true if anything changed
+    fn update_info(&mut self, msg: &Message) -> bool {
+        if let Message::ElevatorInfo {
+            task_id,
+            floor,
+            state,
+            requests,
+        } = msg
+        {
+            for task in self.tasks.iter_mut() {
+                if task.id == *task_id {
+                    let changed =
+                        (task.floor, task.state) != (*floor, *state) || task.requests != *requests;
+                    task.floor = *floor;
+                    task.state = *state;
+                    task.requests = requests.clone();
+                    return changed;
+                }
+            }
+        }
+        false
+    }
+
+    /// Sends a message to a local elevator, marking it as unavailable if it has stopped
+    async fn send_to(&mut self, id: usize, msg: Message) {
+        let Some(task) = self.tasks.iter_mut().find(|task| task.id == id) else {
+            return;
+        };
+        if task.transmitter.send(msg).await.is_err() {
+            eprintln!("Elevator {id} is not running, marking it as unavailable");
+            task.health = Health::Stopped;
+        }
+    }
+
+    async fn send_to_local(&mut self, msg: Message) {
+        for id in self.local_ids.clone() {
+            self.send_to(id, msg.clone()).await;
+        }
+    }
+
+    fn set_health(&mut self, id: usize, health: Health) {
+        if let Some(task) = self.tasks.iter_mut().find(|task| task.id == id) {
+            task.health = health;
+        }
+    }
+
+    fn set_connected(&mut self, id: usize, connected: bool) {
+        if let Some(task) = self.tasks.iter_mut().find(|task| task.id == id) {
+            task.connected = connected;
+        }
+    }
+
+    fn is_available(&self, id: usize) -> bool {
+        self.tasks
+            .iter()
+            .any(|task| task.id == id && task.is_available())
+    }
+
+    /// Nodes that take part in confirming hall requests, always including this one
+    fn connected_nodes(&self) -> Vec<usize> {
+        if self.network.is_none() {
+            return vec![self.node];
+        }
+        self.tasks
+            .iter()
+            .filter(|task| task.connected || task.id == self.node)
+            .map(|task| task.id)
+            .collect()
+    }
+
+    fn local_tasks(&self) -> impl Iterator<Item = &TaskInfo> {
+        self.tasks
+            .iter()
+            .filter(|task| self.local_ids.contains(&task.id))
+    }
+}
+
+async fn recv_optional<T>(rx: &mut Option<Receiver<T>>) -> Option<T> {
+    match rx {
+        Some(rx) => rx.recv().await,
+        None => pending().await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::mpsc;
+
+    use interface::types::floor::N_FLOORS;
+
+    use super::*;
+    use crate::config::{CostWeights, Strategy, Timing};
+    use crate::network::types::Payload;
+
+    /// Latest assignment sent to an elevator, if any
+    fn assignment(rx: &mut Receiver<Message>) -> Option<(u64, Vec<(Floor, Direction)>)> {
+        let mut latest = None;
+        while let Ok(msg) = rx.try_recv() {
+            if let Message::AssignedHallRequests {
+                assignment,
+                requests,
+            } = msg
+            {
+                latest = Some((assignment, requests));
+            }
+        }
+        latest
+    }
+
+    /// Node 0 running elevator 0, with elevator 1 on another node
+    fn networked() -> (Dispatcher, Receiver<Message>, Receiver<Packet>) {
+        N_FLOORS.get_or_init(|| 4);
+
+        let (tx, rx) = mpsc::channel(100);
+        let (tx_packet, rx_packet) = mpsc::channel(100);
+        let remote = crate::network::remote_transmitter(0, 1, tx_packet.clone());
+        let tasks = vec![TaskInfo::new(0, tx), TaskInfo::new(1, remote)];
+        let assigner = assigner::build(
+            Strategy::TimeToIdle,
+            Timing::default(),
+            CostWeights::default(),
+        );
+        let dispatcher = Dispatcher::new(tasks, vec![0], assigner, Some((0, tx_packet)));
+        (dispatcher, rx, rx_packet)
+    }
+
+    fn cab_calls(task_id: usize, session: u64, calls: [bool; 4]) -> NetworkEvent {
+        let calls = calls.to_vec();
+        let msg = Message::CabCalls {
+            task_id,
+            session,
+            calls,
+        };
+        NetworkEvent::Message { from: task_id, msg }
+    }
+
+    /// Cab calls sent back to the other nodes
+    fn restored(rx: &mut Receiver<Packet>) -> Vec<(Option<usize>, Vec<bool>)> {
+        let mut restored = Vec::new();
+        while let Ok(packet) = rx.try_recv() {
+            if let Payload::Message(Message::RestoreCabCalls { calls, .. }) = packet.payload {
+                restored.push((packet.to, calls));
+            }
+        }
+        restored
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn unaccepted_hall_requests_go_to_another_elevator() {
+        N_FLOORS.get_or_init(|| 4);
+
+        let (tx0, mut rx0) = mpsc::channel(100);
+        let (tx1, mut rx1) = mpsc::channel(100);
+        let mut far = TaskInfo::new(1, tx1);
+        far.floor = Floor::from(3);
+        let tasks = vec![TaskInfo::new(0, tx0), far];
+        let assigner = assigner::build(
+            Strategy::TimeToIdle,
+            Timing::default(),
+            CostWeights::default(),
+        );
+        let mut dispatcher = Dispatcher::new(tasks, vec![0, 1], assigner, None);
+
+        // Elevator 0 is the closest, but never answers
+        let call = (Floor::from(0), Direction::Up);
+        let (floor, direction) = call;
+        let request = Message::Request { floor, direction };
+        dispatcher.handle_local(request).await;
+        let (first, requests) = assignment(&mut rx0).unwrap();
+        assert_eq!(requests, vec![call]);
+        let (assignment_1, requests) = assignment(&mut rx1).unwrap();
+        assert!(requests.is_empty());
+        let accepted = Message::HallRequestsAccepted {
+            task_id: 1,
+            assignment: assignment_1,
+        };
+        dispatcher.handle_local(accepted).await;
+
+        tokio::time::advance(ACK_TIMEOUT).await;
+        dispatcher.check_accepted().await;
+        dispatcher.update_hall_requests(true).await;
+        assert_eq!(dispatcher.tasks[0].health, Health::Unresponsive);
+        assert_eq!(dispatcher.tasks[1].health, Health::Healthy);
+        assert_eq!(assignment(&mut rx1).unwrap().1, vec![call]);
+        let order = dispatcher.hall_requests.get(floor, direction);
+        assert_eq!(order.state, OrderState::Assigned { owner: 1 });
+
+        // Answering the assignment it was sent before the reassignment is not enough
+        let accepted = Message::HallRequestsAccepted {
+            task_id: 0,
+            assignment: first,
+        };
+        dispatcher.handle_local(accepted).await;
+        assert_eq!(dispatcher.tasks[0].health, Health::Unresponsive);
+
+        // Answering the latest one makes it available again
+        let (latest, _) = assignment(&mut rx0).unwrap();
+        assert!(latest > first);
+        let accepted = Message::HallRequestsAccepted {
+            task_id: 0,
+            assignment: latest,
+        };
+        dispatcher.handle_local(accepted).await;
+        assert_eq!(dispatcher.tasks[0].health, Health::Healthy);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn accepting_an_older_assignment_does_not_reset_the_deadline() {
+        N_FLOORS.get_or_init(|| 4);
+
+        let (tx, mut rx) = mpsc::channel(100);
+        let assigner = assigner::build(
+            Strategy::TimeToIdle,
+            Timing::default(),
+            CostWeights::default(),
+        );
+        let mut dispatcher = Dispatcher::new(vec![TaskInfo::new(0, tx)], vec![0], assigner, None);
+
+        let request = Message::Request {
+            floor: Floor::from(0),
+            direction: Direction::Up,
+        };
+        dispatcher.handle_local(request).await;
+        let (first, _) = assignment(&mut rx).unwrap();
+
+        tokio::time::advance(ACK_TIMEOUT / 2).await;
+        let request = Message::Request {
+            floor: Floor::from(2),
+            direction: Direction::Down,
+        };
+        dispatcher.handle_local(request).await;
+        let (second, requests) = assignment(&mut rx).unwrap();
+        assert_eq!(requests.len(), 2);
+
+        // The late answer to the first assignment arrives after the second was sent
+        let accepted = Message::HallRequestsAccepted {
+            task_id: 0,
+            assignment: first,
+        };
+        dispatcher.handle_local(accepted).await;
+
+        tokio::time::advance(ACK_TIMEOUT / 2).await;
+        dispatcher.check_accepted().await;
+        assert_eq!(dispatcher.tasks[0].health, Health::Unresponsive);
+        assert!(second > first);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn cab_calls_are_backed_up_by_the_other_nodes() {
+        let (mut dispatcher, _rx, mut rx_packet) = networked();
+
+        // The local elevator's calls are shared with the other nodes
+        let local = Message::CabCalls {
+            task_id: 0,
+            session: 7,
+            calls: vec![false, false, true, false],
+        };
+        dispatcher.handle_local(local.clone()).await;
+        let packet = rx_packet.try_recv().unwrap();
+        assert_eq!(packet.payload, Payload::Message(local));
+
+        // A remote elevator's calls are mirrored, and replaced as they are served
+        let calls = [false, true, false, true];
+        dispatcher.handle_network(cab_calls(1, 3, calls)).await;
+        assert_eq!(dispatcher.cab_calls[&1], (3, calls.to_vec()));
+        let calls = [false, false, false, true];
+        dispatcher.handle_network(cab_calls(1, 3, calls)).await;
+        assert_eq!(dispatcher.cab_calls[&1], (3, calls.to_vec()));
+        assert!(restored(&mut rx_packet).is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn restarted_elevators_get_their_cab_calls_back() {
+        let (mut dispatcher, _rx, mut rx_packet) = networked();
+        let backup = [false, true, false, true];
+        dispatcher.handle_network(cab_calls(1, 3, backup)).await;
+
+        // Restarted with a wiped disk, before it was noticed to have left
+        dispatcher.handle_network(cab_calls(1, 4, [false; 4])).await;
+        assert_eq!(restored(&mut rx_packet), vec![(Some(1), backup.to_vec())]);
+        assert_eq!(dispatcher.cab_calls[&1], (4, backup.to_vec()));
+
+        // Reports from the new session replace the backup as before
+        let calls = [false, false, false, true];
+        dispatcher.handle_network(cab_calls(1, 4, calls)).await;
+        assert_eq!(dispatcher.cab_calls[&1], (4, calls.to_vec()));
+        assert!(restored(&mut rx_packet).is_empty());
+
+        // Nothing is sent back when there is nothing to restore
+        dispatcher.handle_network(cab_calls(1, 4, [false; 4])).await;
+        dispatcher.handle_network(cab_calls(1, 5, [false; 4])).await;
+        assert!(restored(&mut rx_packet).is_empty());
+    }
+}
@@ -0,0 +1,388 @@
+use std::time::Duration;
+
+use interface::types::{Button, Direction, Floor};
+
+use crate::config::{CostWeights, Strategy, Timing};
+use crate::types::elevator::Requests;
+use crate::types::TaskInfo;
+
+/// Active hall request, with the elevator it is currently assigned to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Call {
+    pub floor: Floor,
+    pub direction: Direction,
+    pub owner: Option<usize>,
+}
+
+/// Available elevator, along with the requests it already has to serve
+#[derive(Clone)]
+pub struct Candidate<'a> {
+    pub task: &'a TaskInfo,
+    pub requests: Requests,
+}
+
+/// Strategy for choosing which elevator serves each hall request, see config::Strategy
+pub trait Assigner: Send + Sync {
+    /// Picks the elevator for a call, and returns its id
+    ///
+    /// `elevators` holds every available elevator, and is never empty.
+    fn assign(&mut self, elevators: &[Candidate], call: Call) -> usize;
+
+    /// Gives every active call an owner, and returns the owner of each call
+    ///
+    /// Calls keep their owner as long as it is available, and the rest are handed
+    /// out one at a time with assign(), each seeing the calls handed out before it.
+    fn distribute(&mut self, elevators: &[Candidate], calls: &[Call]) -> Vec<usize> {
+        let mut elevators = elevators.to_vec();
+        let mut owners = vec![None; calls.len()];
+        for (i, call) in calls.iter().enumerate() {
+            if let Some(owner) = elevators.iter_mut().find(|e| Some(e.task.id) == call.owner) {
+                owner.add(call);
+                owners[i] = call.owner;
+            }
+        }
+
+        for (i, call) in calls.iter().enumerate() {
+            if owners[i].is_none() {
+                let id = self.assign(&elevators, *call);
+                if let Some(owner) = elevators.iter_mut().find(|e| e.task.id == id) {
+                    owner.add(call);
+                }
+                owners[i] = Some(id);
+            }
+        }
+
+        owners.into_iter().flatten().collect()
+    }
+}
+
+/// Creates the assigner for a strategy
+pub fn build(strategy: Strategy, timing: Timing, weights: CostWeights) -> Box<dyn Assigner> {
+    match strategy {
+        Strategy::TimeToIdle => Box::new(TimeToIdle { timing, weights }),
+        Strategy::Nearest => Box::new(Nearest { weights }),
+        Strategy::RoundRobin => Box::new(RoundRobin { next: 0 }),
+        Strategy::LeastLoaded => Box::new(LeastLoaded),
+    }
+}
+
+impl Candidate<'_> {
+    fn add(&mut self, call: &Call) {
+        self.requests
+            .add_request(Button::Hall(call.direction), call.floor);
+    }
+}
+
+/// Gives a call to the elevator that would be done the soonest with it, see
+/// TaskInfo::time_to_idle(), settling equal times by the cost function
+pub struct TimeToIdle {
+    timing: Timing,
+    weights: CostWeights,
+}
+
+impl TimeToIdle {
+    fn cost(&self, elevator: &Candidate, call: &Call) -> (Duration, usize) {
+        let mut requests = elevator.requests.clone();
+        requests.add_request(Button::Hall(call.direction), call.floor);
+        let task = elevator.task;
+        (
+            task.time_to_idle(requests, self.timing),
+            task.cost_function_with(
+                &elevator.requests,
+                call.floor,
+                call.direction,
+                &self.weights,
+            ),
+        )
+    }
+
+    /// Time another elevator has to save for a call to be taken from its owner, so that
+    /// calls are not passed back and forth as the estimates change along the way
+    fn handover_margin(&self) -> Duration {
+        self.timing.travel_time + self.timing.door_open_time
+    }
+}
+
+impl Assigner for TimeToIdle {
+    fn assign(&mut self, elevators: &[Candidate], call: Call) -> usize {
+        elevators
+            .iter()
+            .min_by_key(|e| (self.cost(e, &call), e.task.id))
+            .unwrap()
+            .task
+            .id
+    }
+
+    /// Redistributes every call, owned or not, like the hall request assigner
+    ///
+    /// The call and elevator that together give the shortest time to idle are paired
+    /// up one at a time, until every call is taken. A call only leaves its current
+    /// owner if that saves more than handover_margin().
+    fn distribute(&mut self, elevators: &[Candidate], calls: &[Call]) -> Vec<usize> {
+        let mut elevators = elevators.to_vec();
+        let mut owners = vec![None; calls.len()];
+
+        for _ in 0..calls.len() {
+            let unassigned = (0..calls.len()).filter(|&i| owners[i].is_none());
+            let (i, e) = unassigned
+                .flat_map(|i| (0..elevators.len()).map(move |e| (i, e)))
+                .min_by_key(|&(i, e)| {
+                    let (elevator, call) = (&elevators[e], calls[i]);
+                    let (time, cost) = self.cost(elevator, &call);
+                    let handover = match call.owner {
+                        Some(owner) if owner != elevator.task.id => self.handover_margin(),
+                        _ => Duration::ZERO,
+                    };
+                    (time + handover, cost, elevator.task.id)
+                })
+                .unwrap();
+
+            elevators[e].add(&calls[i]);
+            owners[i] = Some(elevators[e].task.id);
+        }
+
+        owners.into_iter().flatten().collect()
+    }
+}
+
+/// Gives a call to the elevator with the lowest cost function, which favours
+/// elevators that are close and already going the right way
+///
+/// The cost is taken with the calls handed out before it, so that a burst of calls
+/// is not all given to the same elevator.
+pub struct Nearest {
+    weights: CostWeights,
+}
+
+impl Assigner for Nearest {
+    fn assign(&mut self, elevators: &[Candidate], call: Call) -> usize {
+        elevators
+            .iter()
+            .min_by_key(|e| {
+                let cost = e.task.cost_function_with(
+                    &e.requests,
+                    call.floor,
+                    call.direction,
+                    &self.weights,
+                );
+                (cost, e.task.id)
+            })
+            .unwrap()
+            .task
+            .id
+    }
+}
+
+/// Gives the calls to each elevator in turn, by id
+pub struct RoundRobin {
+    next: usize,
+}
+
+impl Assigner for RoundRobin {
+    fn assign(&mut self, elevators: &[Candidate], _call: Call) -> usize {
+        let ids = elevators.iter().map(|e| e.task.id);
+        let id = ids
+            .clone()
+            .filter(|&id| id >= self.next)
+            .min()
+            .or_else(|| ids.min())
+            .unwrap();
+        self.next = id + 1;
+        id
+    }
+}
+
+/// Gives a call to the elevator with the fewest requests, and then the closest one
+pub struct LeastLoaded;
+
+impl Assigner for LeastLoaded {
+    fn assign(&mut self, elevators: &[Candidate], call: Call) -> usize {
+        elevators
+            .iter()
+            .min_by_key(|e| {
+                let distance = usize::from(e.task.floor).abs_diff(usize::from(call.floor));
+                (e.requests.number_of_requests(), distance, e.task.id)
+            })
+            .unwrap()
+            .task
+            .id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::mpsc;
+
+    use interface::types::floor::N_FLOORS;
+
+    use super::*;
+    use crate::state_machine::types::State;
+
+    fn elevator(id: usize, floor: usize, cab_calls: &[usize]) -> (TaskInfo, Requests) {
+        N_FLOORS.get_or_init(|| 4);
+
+        let mut task = TaskInfo::new(id, mpsc::channel(1).0);
+        task.floor = Floor::from(floor);
+        let mut requests = Requests::new(4);
+        for &floor in cab_calls {
+            requests.add_request(Button::Cab, Floor::from(floor));
+        }
+        task.requests = requests.clone();
+        (task, requests)
+    }
+
+    fn call(floor: usize, direction: Direction, owner: Option<usize>) -> Call {
+        Call {
+            floor: Floor::from(floor),
+            direction,
+            owner,
+        }
+    }
+
+    fn distribute(
+        strategy: Strategy,
+        elevators: &[(TaskInfo, Requests)],
+        calls: &[Call],
+    ) -> Vec<usize> {
+        let elevators: Vec<_> = elevators
+            .iter()
+            .map(|(task, requests)| Candidate {
+                task,
+                requests: requests.clone(),
+            })
+            .collect();
+        let mut assigner = build(strategy, Timing::default(), CostWeights::default());
+        assigner.distribute(&elevators, calls)
+    }
+
+    #[test]
+    fn calls_are_shared_between_elevators() {
+        let elevators = [elevator(0, 0, &[]), elevator(1, 3, &[])];
+        let calls = [
+            call(1, Direction::Up, None),
+            call(2, Direction::Down, None),
+            call(3, Direction::Down, None),
+        ];
+        let distribute = |strategy| distribute(strategy, &elevators, &calls);
+        assert_eq!(distribute(Strategy::TimeToIdle), vec![0, 1, 1]);
+        assert_eq!(distribute(Strategy::Nearest), vec![0, 1, 1]);
+        assert_eq!(distribute(Strategy::RoundRobin), vec![0, 1, 0]);
+        assert_eq!(distribute(Strategy::LeastLoaded), vec![0, 1, 1]);
+    }
+
+    #[test]
+    fn calls_are_moved_to_an_elevator_that_is_done_sooner() {
+        // Elevator 0 took both calls while 1 was busy, and 1 is now free at the top
+        let mut elevators = [elevator(0, 0, &[]), elevator(1, 3, &[])];
+        elevators[0].0.state = State::Moving(Direction::Up);
+        let calls = [
+            call(1, Direction::Up, Some(0)),
+            call(3, Direction::Down, Some(0)),
+        ];
+        assert_eq!(
+            distribute(Strategy::TimeToIdle, &elevators, &calls),
+            vec![0, 1]
+        );
+
+        // The other strategies only give out calls without an available owner
+        assert_eq!(
+            distribute(Strategy::Nearest, &elevators, &calls),
+            vec![0, 0]
+        );
+        let calls = [
+            call(1, Direction::Up, Some(0)),
+            call(3, Direction::Down, Some(2)),
+        ];
+        assert_eq!(
+            distribute(Strategy::Nearest, &elevators, &calls),
+            vec![0, 1]
+        );
+    }
+
+    #[test]
+    fn current_owner_keeps_calls_unless_much_faster() {
+        let elevators = [elevator(0, 1, &[]), elevator(1, 1, &[])];
+        let calls = [call(3, Direction::Down, Some(1))];
+        assert_eq!(
+            distribute(Strategy::TimeToIdle, &elevators, &calls),
+            vec![1]
+        );
+
+        // One floor closer is not enough
+        let elevators = [elevator(0, 2, &[]), elevator(1, 1, &[])];
+        assert_eq!(
+            distribute(Strategy::TimeToIdle, &elevators, &calls),
+            vec![1]
+        );
+        let elevators = [elevator(0, 3, &[]), elevator(1, 0, &[])];
+        assert_eq!(
+            distribute(Strategy::TimeToIdle, &elevators, &calls),
+            vec![0]
+        );
+    }
+
+    #[test]
+    fn calls_in_one_round_are_not_all_given_to_the_closest_elevator() {
+        // Elevator 0 is the closest to every call, but not once it has the first ones
+        let elevators = [elevator(0, 0, &[]), elevator(1, 3, &[])];
+        let calls = [
+            call(1, Direction::Up, None),
+            call(1, Direction::Down, None),
+            call(0, Direction::Up, None),
+        ];
+        assert_eq!(
+            distribute(Strategy::Nearest, &elevators, &calls),
+            vec![0, 1, 0]
+        );
+
+        // Both already stop at these floors, so equal times to idle are settled by the
+        // cost with the calls handed out so far
+        let mut elevators = [elevator(0, 0, &[2, 3]), elevator(1, 0, &[2, 3])];
+        for (task, _) in elevators.iter_mut() {
+            task.state = State::Moving(Direction::Up);
+        }
+        let calls = [call(2, Direction::Up, None), call(3, Direction::Down, None)];
+        assert_eq!(
+            distribute(Strategy::TimeToIdle, &elevators, &calls),
+            vec![0, 1]
+        );
+    }
+
+    #[test]
+    fn least_loaded_prefers_quiet_elevators_over_close_ones() {
+        let elevators = [elevator(0, 2, &[0, 3]), elevator(1, 0, &[1])];
+        let calls = [call(2, Direction::Up, None), call(1, Direction::Down, None)];
+
+        // The second call goes to the closest one, once both have as many requests
+        let least_loaded = distribute(Strategy::LeastLoaded, &elevators, &calls);
+        assert_eq!(least_loaded, vec![1, 0]);
+        assert_eq!(
+            distribute(Strategy::Nearest, &elevators, &calls),
+            vec![0, 1]
+        );
+    }
+
+    #[test]
+    fn round_robin_skips_unavailable_elevators() {
+        let elevators = [elevator(0, 0, &[]), elevator(2, 0, &[])];
+        let calls = [
+            call(0, Direction::Up, None),
+            call(1, Direction::Up, None),
+            call(2, Direction::Up, None),
+        ];
+        let mut assigner = build(
+            Strategy::RoundRobin,
+            Timing::default(),
+            CostWeights::default(),
+        );
+        let elevators: Vec<_> = elevators
+            .iter()
+            .map(|(task, requests)| Candidate {
+                task,
+                requests: requests.clone(),
+            })
+            .collect();
+        assert_eq!(assigner.distribute(&elevators, &calls), vec![0, 2, 0]);
+        assert_eq!(assigner.assign(&elevators, calls[0]), 2);
+    }
+}
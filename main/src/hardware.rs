@@ -0,0 +1,46 @@
+use std::future::{pending, Future};
+use std::io::Result;
+use std::net::SocketAddr;
+
+use interface::{ElevatorDriver, HardwareDriver};
+
+/// Source of the hardware each elevator is controlled through
+///
+/// The supervisor connects again every time it restarts a state machine.
+pub trait Hardware: Send + Sync + 'static {
+    type Driver: HardwareDriver + 'static;
+
+    fn connect(&self, task_id: usize) -> impl Future<Output = Result<Self::Driver>> + Send;
+
+    /// Describes where the hardware of an elevator is found, used in log messages
+    fn describe(&self, task_id: usize) -> String;
+
+    /// Resolves when the software of an elevator should crash, so that tests can kill
+    /// a single elevator while the others keep running. Never resolves by default.
+    fn crashed(&self, _task_id: usize) -> impl Future<Output = ()> + Send {
+        pending()
+    }
+}
+
+/// Elevators reached over TCP, such as the simulator, elevator `i` at the `i`-th address
+pub struct TcpHardware {
+    addresses: Vec<SocketAddr>,
+}
+
+impl TcpHardware {
+    pub fn new(addresses: Vec<SocketAddr>) -> TcpHardware {
+        TcpHardware { addresses }
+    }
+}
+
+impl Hardware for TcpHardware {
+    type Driver = ElevatorDriver;
+
+    async fn connect(&self, task_id: usize) -> Result<ElevatorDriver> {
+        ElevatorDriver::connect(self.addresses[task_id]).await
+    }
+
+    fn describe(&self, task_id: usize) -> String {
+        self.addresses[task_id].to_string()
+    }
+}
@@ -1,95 +1,104 @@
 #![allow(dead_code)]
 use std::error::Error;
-use std::net::SocketAddr;
-use tokio::net::TcpStream;
+use std::sync::Arc;
 use tokio::sync::mpsc;
 
 use interface::types::Floor;
 
+mod benchmark;
+mod config;
+mod dispatcher;
 mod error;
+mod hardware;
+mod network;
 mod state_machine;
+mod supervisor;
 mod types;
 
-use crate::types::{Message, TaskInfo};
+use crate::dispatcher::{assigner, Dispatcher};
+use crate::supervisor::Supervised;
+use crate::types::{Health, TaskInfo};
 
-pub struct Config {
-    pub n_elevators: usize,
-    pub n_floors: usize,
-}
+pub use crate::benchmark::{Benchmark, Summary, Traffic};
+pub use crate::config::{Config, CostWeights, Network, Strategy, Timing, USAGE};
+pub use crate::hardware::{Hardware, TcpHardware};
 
+/// Runs the elevators, connecting to the simulators or elevators at the configured addresses
 pub async fn run(config: Config) -> Result<(), Box<dyn Error>> {
+    let hardware = TcpHardware::new(config.addresses.clone());
+    run_with(config, hardware).await
+}
+
+/// Same as run(), but with the hardware of the elevators given by the caller
+pub async fn run_with<H: Hardware>(config: Config, hardware: H) -> Result<(), Box<dyn Error>> {
     println!("Executing run function");
 
     let Config {
         n_elevators,
         n_floors,
+        timing,
+        assigner,
+        cost,
+        network,
+        ..
     } = config;
     println!("Number of elevator: {n_elevators}");
     println!("Number of floors: {n_floors}");
+    println!("Assigning hall requests by: {assigner}");
 
     Floor::initialize(n_floors);
 
     let mut tasks = Vec::new();
     let mut handles = Vec::new();
+    let hardware = Arc::new(hardware);
 
-    let (tx_task, mut rx) = mpsc::channel(100);
-
-    const HOST: [u8; 4] = [127, 0, 0, 1];
-    const BASE_PORT: u16 = 10000;
+    let (tx_task, rx) = mpsc::channel(100);
 
-    for i in 0..n_elevators {
-        let addr = SocketAddr::from((HOST, BASE_PORT + i as u16));
-        let stream = TcpStream::connect(addr).await?;
-        println!("task {i} connected to {addr}");
+    for i in config.local_ids() {
         let (tx, rx_task) = mpsc::channel(100);
-        let tx_task = tx_task.clone();
-
-        let handle = tokio::spawn(async move {
-            if let Err(_) = state_machine::run(i, stream, (tx_task, rx_task)).await {
-                println!("Error occured in state machine");
-            }
-        });
-
-        handles.push(handle);
-        tasks.push(TaskInfo::new(i, tx));
+        let elevator = Supervised {
+            task_id: i,
+            hardware: hardware.clone(),
+            timing,
+            state_dir: config.state_dir.clone(),
+        };
+
+        handles.push(tokio::spawn(supervisor::run(elevator, (tx_task.clone(), rx_task))));
+
+        // Unavailable until the supervisor has started the state machine
+        let mut task = TaskInfo::new(i, tx);
+        task.health = Health::Stopped;
+        tasks.push(task);
     }
 
-    while let Some(msg) = rx.recv().await {
-        match msg {
-            Message::Request { floor, direction } => {
-                let tx = &tasks
-                    .iter()
-                    .min_by_key(|x| x.cost_function(floor, direction))
-                    .unwrap()
-                    .transmitter;
-                tx.send(msg).await.unwrap();
-            }
-            Message::HallButtonLight { .. } => {
-                // Send message to all elevators for hall button light
-                for task in tasks.iter() {
-                    task.transmitter.send(msg).await.unwrap();
-                }
-            }
-            Message::ElevatorInfo {
-                task_id,
-                floor,
-                state,
-                n_requests,
-            } => {
-                for task in tasks.iter_mut() {
-                    if task.id == task_id {
-                        task.floor = floor;
-                        task.state = state;
-                        task.n_requests = n_requests;
-                        break;
-                    }
+    // When running a single elevator, the other elevators are reached over the network
+    let (outbox, rx_network) = match config.id {
+        Some(id) => {
+            let (tx_packet, rx_packet) = mpsc::channel(100);
+            let (tx_event, rx_event) = mpsc::channel(100);
+
+            let handle = tokio::spawn(async move {
+                if let Err(e) = network::run(id, network, (tx_event, rx_packet)).await {
+                    eprintln!("Error occured in network => error: {e}");
                 }
+            });
+            handles.push(handle);
+
+            for peer in (0..n_elevators).filter(|&peer| peer != id) {
+                let tx = network::remote_transmitter(id, peer, tx_packet.clone());
+                let mut task = TaskInfo::new(peer, tx);
+                task.connected = false;
+                tasks.push(task);
             }
-            Message::Shutdown => {
-                eprint!("Received shutdown message from task");
-            }
+
+            (Some((id, tx_packet)), Some(rx_event))
         }
-    }
+        None => (None, None),
+    };
+
+    let assigner = assigner::build(assigner, timing, cost);
+    let dispatcher = Dispatcher::new(tasks, config.local_ids(), assigner, outbox);
+    dispatcher.run(rx, rx_network).await;
 
     // CHECK FOR ELEVATOR CRASHES
     for handle in handles {
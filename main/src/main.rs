@@ -1,18 +1,24 @@
-//use std::env;
+use std::env;
 use std::process;
 
 use elevators::Config;
 
 #[tokio::main]
 async fn main() {
-    let config = Config {
-        n_elevators: 2,
-        n_floors: 4,
-    };
+    if env::args().any(|arg| arg == "-h" || arg == "--help") {
+        println!("{}", elevators::USAGE);
+        return;
+    }
+
+    let config = Config::build(env::args()).unwrap_or_else(|err| {
+        eprintln!("Problem parsing arguments: {err}");
+        eprintln!("{}", elevators::USAGE);
+        process::exit(1);
+    });
 
     if let Err(e) = elevators::run(config).await {
         eprintln!("Critical error: {}", e);
 
         process::exit(1);
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,256 @@
+use std::io::Result;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::time::Duration;
+
+use socket2::{Domain, Protocol, Socket, Type};
+use tokio::net::UdpSocket;
+use tokio::sync::mpsc::{self, Receiver, Sender};
+use tokio::time::interval;
+
+use crate::config::Network;
+use crate::types::Message;
+
+mod peers;
+pub mod types;
+
+use self::peers::Peers;
+use self::types::{NetworkEvent, Packet, Payload};
+
+const HEARTBEAT_INTERVAL: Duration = Duration::from_millis(100);
+const PEER_TIMEOUT: Duration = Duration::from_millis(1000);
+/// Largest payload of a UDP datagram over IPv4
+const MAX_PACKET_SIZE: usize = 65507;
+
+/// Runs the network for the elevator with the given id
+///
+/// Packets received on `rx` are broadcast to every other process, together with
+/// a heartbeat every HEARTBEAT_INTERVAL. Messages meant for this elevator, and
+/// peers joining or leaving, are passed on through `tx`.
+pub async fn run(
+    id: usize,
+    settings: Network,
+    (tx, mut rx): (Sender<NetworkEvent>, Receiver<Packet>),
+) -> Result<()> {
+    let socket = bind(settings.port)?;
+    let target = SocketAddr::new(settings.broadcast, settings.port);
+    println!("network: elevator {id} broadcasting on {target}");
+
+    let mut peers = Peers::new();
+    let mut heartbeat = interval(HEARTBEAT_INTERVAL);
+    let mut buffer = vec![0; MAX_PACKET_SIZE];
+
+    loop {
+        tokio::select! {
+            _ = heartbeat.tick() => {
+                send(&socket, target, &Packet::heartbeat(id)).await;
+
+                for peer in peers.remove_lost(PEER_TIMEOUT) {
+                    println!("network: lost connection to elevator {peer}");
+                    let _ = tx.send(NetworkEvent::PeerLeft(peer)).await;
+                }
+            }
+            packet = rx.recv() => match packet {
+                Some(packet) => send(&socket, target, &packet).await,
+                None => return Ok(()),
+            },
+            received = socket.recv_from(&mut buffer) => {
+                let len = match received {
+                    Ok((len, _)) => len,
+                    Err(e) => {
+                        eprintln!("network: failed to receive packet => error: {e}");
+                        continue;
+                    }
+                };
+
+                let packet = match Packet::decode(&buffer[..len]) {
+                    Ok(packet) => packet,
+                    Err(e) => {
+                        eprintln!("network: dropped invalid packet => error: {e}");
+                        continue;
+                    }
+                };
+
+                if packet.from == id {
+                    continue;
+                }
+
+                if peers.heard_from(packet.from) {
+                    println!("network: elevator {} joined", packet.from);
+                    let _ = tx.send(NetworkEvent::PeerJoined(packet.from)).await;
+                }
+
+                if !packet.is_for(id) {
+                    continue;
+                }
+
+                let from = packet.from;
+                let event = match packet.payload {
+                    Payload::Heartbeat => continue,
+                    Payload::Message(msg) => NetworkEvent::Message { from, msg },
+                    Payload::HallRequests(requests) => NetworkEvent::HallRequests { from, requests },
+                };
+                let _ = tx.send(event).await;
+            }
+        }
+    }
+}
+
+/// Creates a transmitter that sends every message to the elevator `to` over the network
+pub fn remote_transmitter(from: usize, to: usize, network: Sender<Packet>) -> Sender<Message> {
+    let (tx, mut rx) = mpsc::channel(100);
+    tokio::spawn(async move {
+        while let Some(msg) = rx.recv().await {
+            if network
+                .send(Packet::message(from, Some(to), msg))
+                .await
+                .is_err()
+            {
+                break;
+            }
+        }
+    });
+    tx
+}
+
+/// Binds a broadcast socket that can be shared by several processes on the same computer
+fn bind(port: u16) -> Result<UdpSocket> {
+    let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+    socket.set_reuse_address(true)?;
+    #[cfg(all(unix, not(target_os = "linux")))]
+    socket.set_reuse_port(true)?;
+    socket.set_broadcast(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&SocketAddr::from((Ipv4Addr::UNSPECIFIED, port)).into())?;
+    UdpSocket::from_std(socket.into())
+}
+
+async fn send(socket: &UdpSocket, target: SocketAddr, packet: &Packet) {
+    let bytes = match packet.encode() {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            eprintln!("network: dropped packet => error: {e}");
+            return;
+        }
+    };
+    if let Err(e) = socket.send_to(&bytes, target).await {
+        eprintln!("network: failed to send packet => error: {e}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use interface::types::floor::N_FLOORS;
+    use interface::types::{Button, Direction, Floor};
+
+    use super::*;
+    use crate::state_machine::types::State;
+    use crate::types::elevator::Requests;
+
+    #[test]
+    fn packet_round_trip() {
+        N_FLOORS.get_or_init(|| 4);
+        let mut requests = Requests::new(4);
+        requests.add_request(Button::Cab, Floor::from(0));
+        requests.add_request(Button::Hall(Direction::Down), Floor::from(3));
+        let matrix =
+            "[[false,false,true],[false,false,false],[false,false,false],[false,true,false]]";
+        assert_eq!(serde_json::to_string(&requests).unwrap(), matrix);
+
+        let packets = [
+            Packet::heartbeat(0),
+            Packet::message(
+                1,
+                Some(0),
+                Message::Request {
+                    floor: Floor::from(3),
+                    direction: Direction::Down,
+                },
+            ),
+            Packet::message(
+                2,
+                None,
+                Message::ElevatorInfo {
+                    task_id: 2,
+                    floor: Floor::from(1),
+                    state: State::Moving(Direction::Up),
+                    requests,
+                },
+            ),
+            Packet::message(
+                0,
+                Some(2),
+                Message::RestoreCabCalls {
+                    task_id: 2,
+                    calls: vec![true, false, false, true],
+                },
+            ),
+        ];
+
+        for packet in packets {
+            assert_eq!(Packet::decode(&packet.encode().unwrap()).unwrap(), packet);
+        }
+        assert!(Packet::decode(br#"{"from":0,"to":null,"payload":{"Message":{"Request":{"floor":9,"direction":"Up"}}}}"#).is_err());
+    }
+
+    #[test]
+    fn requests_for_another_floor_count_are_rejected() {
+        N_FLOORS.get_or_init(|| 4);
+        let info = |rows: &str| {
+            format!(
+                r#"{{"from":1,"to":null,"payload":{{"Message":{{"ElevatorInfo":{{"task_id":1,"floor":0,"state":"Idle","requests":[{rows}]}}}}}}}}"#
+            )
+        };
+        let row = "[false,true,false]";
+
+        let packet = Packet::decode(info(&[row; 4].join(",")).as_bytes()).unwrap();
+        let Payload::Message(Message::ElevatorInfo { requests, .. }) = packet.payload else {
+            panic!("decoded {packet:?}");
+        };
+        assert!(requests.has_request(Button::Hall(Direction::Down), Floor::from(3)));
+
+        for n_rows in [0, 3, 5] {
+            let packet = info(&vec![row; n_rows].join(","));
+            assert!(Packet::decode(packet.as_bytes()).is_err(), "{n_rows} rows");
+        }
+    }
+
+    #[test]
+    fn oversized_packets_are_not_sent() {
+        let calls = |n| Message::RestoreCabCalls {
+            task_id: 0,
+            calls: vec![true; n],
+        };
+        let packet = Packet::message(0, Some(1), calls(10_000));
+        assert!(packet.encode().unwrap().len() > 4096);
+
+        let packet = Packet::message(0, Some(1), calls(MAX_PACKET_SIZE / 5));
+        let err = packet.encode().unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn packet_recipients() {
+        let packet = Packet::heartbeat(1);
+        assert!(packet.is_for(0));
+        assert!(!packet.is_for(1));
+
+        let packet = Packet::message(1, Some(2), Message::Shutdown);
+        assert!(packet.is_for(2));
+        assert!(!packet.is_for(0));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn peers_time_out() {
+        let mut peers = Peers::new();
+        assert!(peers.heard_from(1));
+        assert!(!peers.heard_from(1));
+
+        tokio::time::advance(PEER_TIMEOUT / 2).await;
+        assert!(peers.heard_from(2));
+        assert!(peers.remove_lost(PEER_TIMEOUT).is_empty());
+
+        tokio::time::advance(PEER_TIMEOUT).await;
+        assert_eq!(peers.remove_lost(PEER_TIMEOUT), vec![1]);
+        assert!(peers.contains(2));
+    }
+}
@@ -0,0 +1,39 @@
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Keeps track of when each peer was last heard from
+#[derive(Debug, Default)]
+pub struct Peers {
+    last_seen: HashMap<usize, Instant>,
+}
+
+impl Peers {
+    pub fn new() -> Peers {
+        Peers::default()
+    }
+
+    /// Registers a packet from the peer, returns true if the peer is new
+    pub fn heard_from(&mut self, id: usize) -> bool {
+        self.last_seen.insert(id, Instant::now()).is_none()
+    }
+
+    /// Removes every peer that has not been heard from within the timeout
+    pub fn remove_lost(&mut self, timeout: Duration) -> Vec<usize> {
+        let lost: Vec<usize> = self
+            .last_seen
+            .iter()
+            .filter(|(_, seen)| seen.elapsed() > timeout)
+            .map(|(&id, _)| id)
+            .collect();
+
+        for id in lost.iter() {
+            self.last_seen.remove(id);
+        }
+        lost
+    }
+
+    pub fn contains(&self, id: usize) -> bool {
+        self.last_seen.contains_key(&id)
+    }
+}
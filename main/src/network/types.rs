@@ -0,0 +1,78 @@
+use std::io::{self, ErrorKind};
+
+use serde::{Deserialize, Serialize};
+
+use super::MAX_PACKET_SIZE;
+use crate::types::{HallRequests, Message};
+
+/// Datagram sent between the processes, encoded as JSON
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Packet {
+    pub from: usize,
+    pub to: Option<usize>,
+    pub payload: Payload,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Payload {
+    Heartbeat,
+    Message(Message),
+    HallRequests(HallRequests),
+}
+
+/// Events passed from the network task to the dispatcher
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NetworkEvent {
+    Message { from: usize, msg: Message },
+    HallRequests { from: usize, requests: HallRequests },
+    PeerJoined(usize),
+    PeerLeft(usize),
+}
+
+impl Packet {
+    pub fn heartbeat(from: usize) -> Packet {
+        Packet {
+            from,
+            to: None,
+            payload: Payload::Heartbeat,
+        }
+    }
+
+    pub fn message(from: usize, to: Option<usize>, msg: Message) -> Packet {
+        Packet {
+            from,
+            to,
+            payload: Payload::Message(msg),
+        }
+    }
+
+    pub fn hall_requests(from: usize, requests: HallRequests) -> Packet {
+        Packet {
+            from,
+            to: None,
+            payload: Payload::HallRequests(requests),
+        }
+    }
+
+    /// Encodes the packet, failing if it does not fit in a single datagram
+    pub fn encode(&self) -> io::Result<Vec<u8>> {
+        let bytes = serde_json::to_vec(self).expect("packets are always serializable");
+        if bytes.len() > MAX_PACKET_SIZE {
+            let msg = format!(
+                "packet of {} bytes is larger than the maximum of {MAX_PACKET_SIZE} bytes",
+                bytes.len()
+            );
+            return Err(io::Error::new(ErrorKind::InvalidInput, msg));
+        }
+        Ok(bytes)
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Packet, serde_json::Error> {
+        serde_json::from_slice(bytes)
+    }
+
+    /// Checks if the packet is meant for the elevator with the given id
+    pub fn is_for(&self, id: usize) -> bool {
+        self.from != id && self.to.is_none_or(|to| to == id)
+    }
+}
@@ -1,27 +1,32 @@
-use tokio::sync::mpsc::{Receiver, Sender};
-use std::time::Duration;
-use tokio::time::sleep;
-use tokio::net::TcpStream;
+use std::future::pending;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::mpsc::{self, Receiver, Sender};
+use tokio::time::{sleep, sleep_until};
 
 use interface::types::{Button, Direction, Floor};
-use interface::{get, send};
+use interface::{Edge, HardwareDriver, SharedDriver};
 
+use crate::config::Timing;
 use crate::error::ElevatorError;
+use crate::types::elevator::{CabStorage, Timer};
 use crate::types::{Elevator, Message};
 
 mod handle;
+mod poller;
+pub mod simulate;
 pub mod types;
 
-use self::types::{Event, State};
-
-const TIME_BETWEEN_EVENT_CHECKS: u64 = 10; // in milliseconds
+use self::types::{Event, Input, State};
 
 pub async fn run(
     task_id: usize,
-    mut stream: TcpStream,
+    driver: impl HardwareDriver + 'static,
     (tx, mut rx): (Sender<Message>, Receiver<Message>),
+    timing: Timing,
+    mut storage: CabStorage,
 ) -> Result<(), ElevatorError> {
-    let start_floor = initialize(&mut stream).await.map_err(|e| {
+    let mut driver = SharedDriver::new(driver);
+    let start_floor = initialize(&mut driver, timing.poll_interval).await.map_err(|e| {
         eprintln!("Could not start up elevator: {e}");
         ElevatorError {
             floor: Floor::from(0),
@@ -29,38 +34,114 @@ pub async fn run(
             critical: true,
         }
     })?;
-    let mut elevator = Elevator::new(start_floor);
+    let mut elevator = Elevator::new(start_floor, timing);
+
+    // Stops by itself once `inputs` is dropped
+    let (input_tx, mut inputs) = mpsc::channel(100);
+    let poller = poller::run(task_id, driver.clone(), input_tx, timing);
+    tokio::spawn(poller);
+
+    // Cab calls from before a crash or power loss are served first
+    let mut cab_calls = storage.load(Floor::get_n_floors());
+    for (floor, _) in cab_calls.iter().enumerate().filter(|(_, &call)| call) {
+        let floor = Floor::from(floor);
+        println!("task {task_id}: Restoring cab call at floor {floor}");
+        handle::button_press(&mut driver, &tx, &mut elevator, Button::Cab, floor).await;
+    }
+    let session = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_nanos() as u64);
+    let calls = cab_calls.clone();
+    let msg = Message::CabCalls {
+        task_id,
+        session,
+        calls,
+    };
+    tx.send(msg).await.unwrap();
+
+    // Available for hall requests from here on
+    let mut health = elevator.health();
+    tx.send(Message::Health { task_id, health }).await.unwrap();
 
     loop {
-        let event = wait_for_event(task_id, &mut stream, (&tx, &mut rx), &elevator).await;
+        let event = wait_for_event(task_id, (&tx, &mut rx), &mut inputs, &elevator).await;
 
         match event {
             Event::ArriveAtFloor(floor) => {
-                handle::arrive_at_floor(&mut stream, &tx, &mut elevator, floor).await;
+                handle::arrive_at_floor(&mut driver, &tx, &mut elevator, floor).await;
             }
             Event::TimerTimedOut => {
-                handle::timer_timed_out(&mut stream, &tx, &mut elevator).await;
+                handle::timer_timed_out(&mut driver, &tx, &mut elevator).await;
             }
             Event::MessageReceived(msg) => {
-                handle::message_received(&mut stream, &mut elevator, msg).await?;
+                let accepted = match msg {
+                    Message::AssignedHallRequests { assignment, .. } => Some(assignment),
+                    _ => None,
+                };
+                handle::message_received(&mut driver, &mut elevator, msg).await?;
+
+                // The dispatcher gives the requests to another elevator if this is not sent
+                if let Some(assignment) = accepted {
+                    let msg = Message::HallRequestsAccepted {
+                        task_id,
+                        assignment,
+                    };
+                    tx.send(msg).await.unwrap();
+                }
             }
             Event::ButtonPress(button, floor) => {
-                handle::button_press(&mut stream, &tx, &mut elevator, button, floor).await;
+                handle::button_press(&mut driver, &tx, &mut elevator, button, floor).await;
+            }
+            Event::Obstruction(obstructed) => {
+                handle::obstruction(&mut elevator, obstructed);
+            }
+            Event::StopButton(pressed) => {
+                handle::stop_button(&mut driver, &mut elevator, pressed).await;
+            }
+            Event::MotorFault(fault) => {
+                handle::motor_fault(&mut driver, &mut elevator, fault).await;
             }
+            Event::ConnectionChanged(connected) => {
+                elevator.connected = connected;
+            }
+        }
+
+        // Let the hall requests go to other elevators while this one can not serve them
+        if elevator.health() != health {
+            health = elevator.health();
+            let msg = Message::Health { task_id, health };
+            tx.send(msg).await.unwrap();
         }
 
         if elevator.state == State::Idle {
-            handle::try_move(&mut stream, &tx, &mut elevator)
+            handle::try_move(&mut driver, &tx, &mut elevator)
                 .await
                 .err();
         }
+
+        if let Err(e) = storage.save(&elevator.requests.get_cab_requests()) {
+            eprintln!("task {task_id}: Failed to store cab calls => error: {e}");
+        }
+
+        // Let the other elevators keep a backup of the cab calls
+        let calls = elevator.requests.get_cab_requests();
+        if calls != cab_calls {
+            cab_calls = calls.clone();
+            let msg = Message::CabCalls {
+                task_id,
+                session,
+                calls,
+            };
+            tx.send(msg).await.unwrap();
+        }
     }
 }
 
+/// Waits for the next event, while the hardware is polled by the poller task
 async fn wait_for_event(
     task_id: usize,
-    stream: &mut TcpStream,
     (tx, rx): (&Sender<Message>, &mut Receiver<Message>),
+    inputs: &mut Receiver<Input>,
     elevator: &Elevator,
 ) -> Event {
     println!(
@@ -72,73 +153,103 @@ async fn wait_for_event(
         task_id,
         floor: elevator.floor,
         state: elevator.state,
-        n_requests: elevator.requests.number_of_requests(),
+        requests: elevator.requests.clone(),
     };
     tx.send(msg).await.unwrap();
 
+    let motor_timer = elevator.motor_timer.filter(|_| !elevator.motor_fault);
+
     loop {
-        // CHECK FOR FLOOR ARRIVAL
-        if let Ok(opt_floor) = get::floor(stream).await {
-            if let Some(floor) = opt_floor {
-                if floor != elevator.floor {
-                    println!("task {task_id}: Arrival at floor {floor}");
-                    return Event::ArriveAtFloor(floor);
+        tokio::select! {
+            biased;
+
+            Some(input) = inputs.recv() => {
+                if let Some(event) = input_event(task_id, elevator, input) {
+                    return event;
                 }
             }
-        } else {
-            eprintln!("caught error in get::floor()!");
-        }
-
-        // CHECK FOR TIMER
-        if let Some(timer) = elevator.timer {
-            if timer.is_done() {
+            _ = expire(motor_timer) => {
+                eprintln!("task {task_id}: No floor reached in time, the motor may be broken");
+                return Event::MotorFault(true);
+            }
+            _ = expire(elevator.timer) => {
                 eprintln!("task {task_id}: Timer finished");
                 return Event::TimerTimedOut;
             }
+            Some(msg) = rx.recv() => {
+                eprintln!("task {task_id}: Message received: {:?}", msg);
+                return Event::MessageReceived(msg);
+            }
         }
+    }
+}
 
-        // CHECK FOR MESSAGES
-        if let Ok(msg) = rx.try_recv() {
-            eprintln!("task {task_id}: Message received: {:?}", msg);
-            return Event::MessageReceived(msg);
+/// The event caused by a change of the inputs, if any
+fn input_event(task_id: usize, elevator: &Elevator, input: Input) -> Option<Event> {
+    match input {
+        Input::Floor(Some(floor)) if floor != elevator.floor => {
+            println!("task {task_id}: Arrival at floor {floor}");
+            Some(Event::ArriveAtFloor(floor))
         }
-
-        // CHECK FOR BUTTON PRESS
-        for button in Button::iterator() {
-            let floors = elevator.requests.get_active_buttons(button);
-
-            for floor in floors {
-                if let Ok(pressed) = get::order_button(stream, button, floor).await {
-                    if pressed {
-                        println!(
-                            "task {task_id}: Button {:?} was pressed at floor {}",
-                            button, floor
-                        );
-                        return Event::ButtonPress(button, floor);
-                    }
-                } else {
-                    let identifier = format!("floor {floor} & button {button:?}");
-                    eprintln!("caught error in get::order_button() for {identifier}");
-                }
-            }
+        Input::Floor(reading) if elevator.motor_fault && reading != elevator.stuck_at => {
+            println!("task {task_id}: Car is moving again");
+            Some(Event::MotorFault(false))
         }
+        Input::Floor(_) => None,
+        Input::Obstruction(obstructed) if obstructed != elevator.obstructed => {
+            println!("task {task_id}: Obstruction switch is {}", on_off(obstructed));
+            Some(Event::Obstruction(obstructed))
+        }
+        Input::StopButton(pressed) if pressed != elevator.stop_button => {
+            println!("task {task_id}: Stop button is {}", on_off(pressed));
+            Some(Event::StopButton(pressed))
+        }
+        Input::Connection(connected) if connected != elevator.connected => {
+            Some(Event::ConnectionChanged(connected))
+        }
+        Input::Button(button, floor, Edge::Pressed) => {
+            println!("task {task_id}: Button {:?} was pressed at floor {}", button, floor);
+            Some(Event::ButtonPress(button, floor))
+        }
+        Input::Button(_, _, Edge::Released) => None,
+        Input::Obstruction(_) | Input::StopButton(_) | Input::Connection(_) => None,
+    }
+}
 
-        // If no events are found, wait a tiny amount of time before checking for new requests
-        sleep(Duration::from_millis(TIME_BETWEEN_EVENT_CHECKS)).await;
+/// Completes when the timer is done, or never if there is no timer
+async fn expire(timer: Option<Timer>) {
+    match timer {
+        Some(timer) => sleep_until(timer.deadline()).await,
+        None => pending().await,
     }
 }
 
-async fn initialize(stream: &mut TcpStream) -> Result<Floor, std::io::Error> {
-    if let Some(floor) = get::floor(stream).await? {
-        send::floor_indicator(stream, floor).await?;
+/// Finds the current floor, moving down to the nearest floor if between floors
+async fn initialize(
+    driver: &mut impl HardwareDriver,
+    poll_interval: Duration,
+) -> Result<Floor, std::io::Error> {
+    // The motor may still be running if the previous state machine crashed
+    if let Some(floor) = driver.floor().await? {
+        driver.stop().await?;
+        driver.floor_indicator(floor).await?;
         return Ok(floor);
     }
-    send::motor_direction(stream, Direction::Down).await?;
+    driver.motor_direction(Direction::Down).await?;
     loop {
-        if let Some(floor) = get::floor(stream).await? {
-            send::stop(stream).await?;
-            send::floor_indicator(stream, floor).await?;
+        if let Some(floor) = driver.floor().await? {
+            driver.stop().await?;
+            driver.floor_indicator(floor).await?;
             return Ok(floor);
         }
+        sleep(poll_interval).await;
+    }
+}
+
+fn on_off(on: bool) -> &'static str {
+    if on {
+        "on"
+    } else {
+        "off"
     }
 }
@@ -1,10 +1,9 @@
 use std::time::Duration;
-use tokio::net::TcpStream;
 use tokio::sync::mpsc::Sender;
-use tokio::time::sleep;
+use tokio::time::{sleep, Instant};
 
-use interface::send;
 use interface::types::{Button, Direction, Floor};
+use interface::HardwareDriver;
 
 use crate::error::{ElevatorError, Logger};
 use crate::types::elevator::Timer;
@@ -14,19 +13,17 @@ use super::types::State;
 
 type Critical = bool;
 
-const TIME_WAIT_ON_FLOOR: u64 = 3; // in seconds
-
 pub async fn arrive_at_floor(
-    stream: &mut TcpStream,
+    driver: &mut impl HardwareDriver,
     tx: &Sender<Message>,
     elevator: &mut Elevator,
     floor: Floor,
 ) {
     elevator.floor = floor;
+    elevator.motor_timer = None;
+    elevator.motor_fault = false;
 
-    send::floor_indicator(stream, elevator.floor)
-        .await
-        .log_if_err();
+    driver.floor_indicator(elevator.floor).await.log_if_err();
 
     let direction = match elevator.state {
         State::Moving(dir) => dir,
@@ -39,26 +36,40 @@ pub async fn arrive_at_floor(
 
     let direction = match check_for_stop(elevator, direction) {
         Ok(dir) => dir,
-        Err(_) => return,
+        Err(_) if elevator.requests.check_in_direction(floor, direction) => {
+            watch_motor(elevator);
+            return;
+        }
+        Err(_) => {
+            // The requests ahead were given to another elevator while on the way
+            if driver.stop().await.is_err() {
+                eprintln!("Failed to stop at floor {:?}", elevator.floor);
+                return;
+            }
+            elevator.state = State::Idle;
+            return;
+        }
     };
 
-    if let Err(_) = send::stop(stream).await {
+    if driver.stop().await.is_err() {
         eprintln!("Failed to stop at floor {:?}", elevator.floor);
         return;
     }
 
-    wait_at_floor(stream, tx, elevator, direction).await;
+    wait_at_floor(driver, tx, elevator, direction).await;
 }
 
 pub async fn message_received(
-    stream: &mut TcpStream,
+    driver: &mut impl HardwareDriver,
     elevator: &mut Elevator,
     msg: Message,
 ) -> Result<(), ElevatorError> {
     match msg {
-        Message::Request { floor, direction } => {
-            let button = Button::Hall(direction);
-            elevator.requests.add_request(button, floor);
+        Message::Request { .. } => {
+            eprintln!("Hall requests are only taken from AssignedHallRequests, ignoring {msg:?}");
+        }
+        Message::AssignedHallRequests { requests, .. } => {
+            elevator.requests.set_hall_requests(&requests);
         }
         Message::HallButtonLight {
             floor,
@@ -66,14 +77,30 @@ pub async fn message_received(
             on,
         } => {
             let button = Button::Hall(direction);
-            send::order_button_light(stream, button, floor, on)
+            driver
+                .order_button_light(button, floor, on)
                 .await
                 .log_if_err();
-            elevator.requests.update_active_button(button, floor, !on);
+
+            // The request may have been served by another elevator
+            if !on {
+                elevator.requests.get_request(floor, button);
+            }
+        }
+        Message::RestoreCabCalls { calls, .. } => {
+            let floors = calls.iter().enumerate().filter(|(_, &call)| call);
+            for floor in floors.filter_map(|(floor, _)| Floor::from_value(floor)) {
+                println!("Restoring cab call at floor {floor} from backup");
+                add_cab_call(driver, elevator, floor).await;
+            }
         }
         Message::ElevatorInfo { .. } => {
             eprintln!("Main thread sent elevator info...");
         }
+        // Reports meant for the dispatcher, with nothing for the elevator to do
+        Message::CabCalls { .. }
+        | Message::Health { .. }
+        | Message::HallRequestsAccepted { .. } => {}
         Message::Shutdown => return Err(elevator.error(false)),
     }
 
@@ -81,52 +108,128 @@ pub async fn message_received(
 }
 
 pub async fn button_press(
-    stream: &mut TcpStream,
+    driver: &mut impl HardwareDriver,
     tx: &Sender<Message>,
     elevator: &mut Elevator,
     button: Button,
     floor: Floor,
 ) {
     match button {
-        Button::Cab => {
-            elevator.requests.add_request(button, floor);
-            send::order_button_light(stream, button, floor, true)
-                .await
-                .log_if_err();
-            elevator.requests.update_active_button(button, floor, false);
-        }
+        Button::Cab => add_cab_call(driver, elevator, floor).await,
         Button::Hall(direction) => {
-            // Send hall button light message to main thread
-            let msg = Message::HallButtonLight {
-                floor,
-                direction,
-                on: true,
-            };
-            tx.send(msg).await.unwrap();
-
-            // Send request to main thread
+            // Send request to main thread, which turns on the light once the request is confirmed
             let msg = Message::Request { floor, direction };
             tx.send(msg).await.unwrap();
         }
     }
 }
 
+async fn add_cab_call(driver: &mut impl HardwareDriver, elevator: &mut Elevator, floor: Floor) {
+    elevator.requests.add_request(Button::Cab, floor);
+    driver
+        .order_button_light(Button::Cab, floor, true)
+        .await
+        .log_if_err();
+}
+
+pub fn obstruction(elevator: &mut Elevator, obstructed: bool) {
+    elevator.obstructed = obstructed;
+    if !obstructed {
+        elevator.door_blocked_since = None;
+    }
+
+    // An open door is kept open for the full duration after the obstruction changes
+    if elevator.timer.is_some() {
+        elevator.timer = Some(Timer::new(elevator.timing.door_open_time));
+    }
+}
+
+/// Halts the car when the stop button is pressed, and resumes when it is pressed again
+pub async fn stop_button(driver: &mut impl HardwareDriver, elevator: &mut Elevator, pressed: bool) {
+    elevator.stop_button = pressed;
+    if !pressed {
+        return;
+    }
+
+    if elevator.state == State::EmergencyStop {
+        resume(driver, elevator).await;
+    } else {
+        emergency_stop(driver, elevator).await;
+    }
+}
+
+async fn emergency_stop(driver: &mut impl HardwareDriver, elevator: &mut Elevator) {
+    println!("Emergency stop");
+    driver.stop().await.log_if_err();
+    driver.stop_button_light(true).await.log_if_err();
+
+    elevator.state = State::EmergencyStop;
+    elevator.timer = None;
+    elevator.motor_timer = None;
+    elevator.motor_fault = false;
+    elevator.door_blocked_since = None;
+
+    // Passengers can only get out if the car is at a floor
+    let at_floor = matches!(driver.floor().await, Ok(Some(_)));
+    driver.door_open_light(at_floor).await.log_if_err();
+}
+
+async fn resume(driver: &mut impl HardwareDriver, elevator: &mut Elevator) {
+    println!("Resuming after emergency stop");
+    driver.stop_button_light(false).await.log_if_err();
+    driver.door_open_light(false).await.log_if_err();
+
+    // The car may have been stopped between floors, so find the floor again
+    match super::initialize(driver, elevator.timing.poll_interval).await {
+        Ok(floor) => elevator.floor = floor,
+        Err(e) => eprintln!("Failed to find floor after emergency stop => error: {e}"),
+    }
+    elevator.state = State::Idle;
+}
+
+/// Marks the motor as broken when no floor was reached in time, or as working
+/// when the car starts moving again
+pub async fn motor_fault(driver: &mut impl HardwareDriver, elevator: &mut Elevator, fault: bool) {
+    elevator.motor_fault = fault;
+    if fault {
+        elevator.stuck_at = driver.floor().await.ok().flatten();
+    } else {
+        watch_motor(elevator);
+    }
+}
+
 pub async fn timer_timed_out(
-    stream: &mut TcpStream,
+    driver: &mut impl HardwareDriver,
     tx: &Sender<Message>,
     elevator: &mut Elevator,
 ) {
+    if elevator.obstructed {
+        println!("Door is obstructed, keeping it open");
+
+        // Requests for this floor made while the door is open are still served
+        if let State::Still(direction) = elevator.state {
+            if let Ok(direction) = check_for_stop(elevator, direction) {
+                wait_at_floor(driver, tx, elevator, direction).await;
+            }
+        }
+
+        elevator.door_blocked_since.get_or_insert_with(Instant::now);
+        elevator.timer = Some(Timer::new(elevator.timing.door_open_time));
+        return;
+    }
+
     elevator.timer = None;
+    elevator.door_blocked_since = None;
 
-    send::door_open_light(stream, false).await.log_if_err();
+    driver.door_open_light(false).await.log_if_err();
 
     if let State::Still(direction) = elevator.state {
         if let Ok(direction) = check_for_stop(elevator, direction) {
-            wait_at_floor(stream, tx, elevator, direction).await;
+            wait_at_floor(driver, tx, elevator, direction).await;
             return;
         }
 
-        if let Err(_) = try_continue(stream, elevator, direction).await {
+        if try_continue(driver, elevator, direction).await.is_err() {
             elevator.state = State::Idle;
         };
     } else {
@@ -135,7 +238,7 @@ pub async fn timer_timed_out(
 }
 
 pub async fn try_move(
-    stream: &mut TcpStream,
+    driver: &mut impl HardwareDriver,
     tx: &Sender<Message>,
     elevator: &mut Elevator,
 ) -> Result<(), Critical> {
@@ -144,16 +247,16 @@ pub async fn try_move(
     for direction in Direction::iterator() {
         if let Ok(direction) = check_for_stop(elevator, direction) {
             println!("Found request at current floor, direction: {direction}");
-            wait_at_floor(stream, tx, elevator, direction).await;
+            wait_at_floor(driver, tx, elevator, direction).await;
             return Ok(());
         }
     }
 
-    let direction = check_in_both_directions(&elevator).map_err(|_| false)?;
+    let direction = check_in_both_directions(elevator).map_err(|_| false)?;
 
     println!("Request found in direction: {direction}");
 
-    if let Err(e) = send::motor_direction(stream, direction).await {
+    if let Err(e) = driver.motor_direction(direction).await {
         eprintln!("{e}");
         return Err(true);
     };
@@ -161,6 +264,7 @@ pub async fn try_move(
     println!("Succesfully sent motor direction: {direction}");
 
     elevator.state = State::Moving(direction);
+    watch_motor(elevator);
     Ok(())
 }
 
@@ -174,7 +278,7 @@ pub async fn try_move(
 // Hall request in the correct direction => direction
 // Hall request in the opposite direction and no more requests in the current direction => opposite direction
 // Cab request => direction
-fn check_for_stop(elevator: &mut Elevator, direction: Direction) -> Result<Direction, ()> {
+pub fn check_for_stop(elevator: &mut Elevator, direction: Direction) -> Result<Direction, ()> {
     let requests = &mut elevator.requests;
     let mut requests_at_floor_in_direction =
         requests.get_requests_at_floor(elevator.floor, direction);
@@ -195,13 +299,12 @@ fn check_for_stop(elevator: &mut Elevator, direction: Direction) -> Result<Direc
     // Check if there are no more requests in direction
     // If true, check if there are any requests at the floor in the opposite direction
     // If true, return with opposite direction as result
-    if !requests.check_in_direction(elevator.floor, direction) {
-        if !requests
+    if !requests.check_in_direction(elevator.floor, direction)
+        && !requests
             .get_requests_at_floor(elevator.floor, direction.opposite())
             .is_empty()
-        {
-            return Ok(direction.opposite());
-        }
+    {
+        return Ok(direction.opposite());
     }
 
     // Returns Ok(direction) if there was a cab request
@@ -209,7 +312,7 @@ fn check_for_stop(elevator: &mut Elevator, direction: Direction) -> Result<Direc
     result
 }
 
-fn check_in_both_directions(elevator: &Elevator) -> Result<Direction, ()> {
+pub fn check_in_both_directions(elevator: &Elevator) -> Result<Direction, ()> {
     for direction in Direction::iterator() {
         if elevator
             .requests
@@ -222,24 +325,22 @@ fn check_in_both_directions(elevator: &Elevator) -> Result<Direction, ()> {
 }
 
 async fn wait_at_floor(
-    stream: &mut TcpStream,
+    driver: &mut impl HardwareDriver,
     tx: &Sender<Message>,
     elevator: &mut Elevator,
     direction: Direction,
 ) {
     elevator.state = State::Still(direction);
-    elevator.timer = Some(Timer::from_secs(TIME_WAIT_ON_FLOOR));
+    elevator.timer = Some(Timer::new(elevator.timing.door_open_time));
 
     // wait for a short duration to give the button lights some time to shine, literally
     sleep(Duration::from_millis(50)).await;
 
-    send::door_open_light(stream, true).await.log_if_err();
-    send::order_button_light(stream, Button::Cab, elevator.floor, false)
+    driver.door_open_light(true).await.log_if_err();
+    driver
+        .order_button_light(Button::Cab, elevator.floor, false)
         .await
         .log_if_err();
-    elevator
-        .requests
-        .update_active_button(Button::Cab, elevator.floor, true);
 
     let msg = Message::HallButtonLight {
         floor: elevator.floor,
@@ -250,7 +351,7 @@ async fn wait_at_floor(
 }
 
 async fn try_continue(
-    stream: &mut TcpStream,
+    driver: &mut impl HardwareDriver,
     elevator: &mut Elevator,
     direction: Direction,
 ) -> Result<(), Critical> {
@@ -262,7 +363,7 @@ async fn try_continue(
         return Err(false);
     }
 
-    if let Err(_) = send::motor_direction(stream, direction).await {
+    if driver.motor_direction(direction).await.is_err() {
         eprintln!(
             "failed to move in direction {:?} from {:?}",
             direction, elevator.floor
@@ -271,5 +372,260 @@ async fn try_continue(
     }
 
     elevator.state = State::Moving(direction);
-    return Ok(());
+    watch_motor(elevator);
+    Ok(())
+}
+
+/// Expects the car to reach the next floor within the travel timeout
+fn watch_motor(elevator: &mut Elevator) {
+    elevator.motor_timer = Some(Timer::new(elevator.timing.travel_timeout));
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::mpsc::{self, Receiver};
+
+    use interface::types::floor::N_FLOORS;
+    use interface::MockDriver;
+
+    use super::*;
+    use crate::config::Timing;
+    use crate::types::Health;
+
+    fn setup(
+        floor: usize,
+        state: State,
+    ) -> (Elevator, MockDriver, Sender<Message>, Receiver<Message>) {
+        N_FLOORS.get_or_init(|| 4);
+
+        let mut elevator = Elevator::new(Floor::from(floor), Timing::default());
+        elevator.state = state;
+        let driver = MockDriver::at_floor(Floor::from(floor));
+        let (tx, rx) = mpsc::channel(10);
+        (elevator, driver, tx, rx)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn stops_for_hall_request_in_direction() {
+        let (mut elevator, mut driver, tx, mut rx) = setup(1, State::Moving(Direction::Up));
+        driver.motor = Some(Direction::Up);
+        let floor = Floor::from(2);
+        elevator
+            .requests
+            .add_request(Button::Hall(Direction::Up), floor);
+
+        arrive_at_floor(&mut driver, &tx, &mut elevator, floor).await;
+
+        assert_eq!(elevator.state, State::Still(Direction::Up));
+        assert!(elevator.timer.is_some());
+        assert_eq!(driver.motor, None);
+        assert_eq!(driver.floor_indicator, Some(floor));
+        assert!(driver.door_open_light);
+
+        let msg = rx.recv().await.unwrap();
+        let served = Message::HallButtonLight {
+            floor,
+            direction: Direction::Up,
+            on: false,
+        };
+        assert_eq!(msg, served);
+    }
+
+    #[tokio::test]
+    async fn passes_floor_without_requests() {
+        let (mut elevator, mut driver, tx, mut rx) = setup(1, State::Moving(Direction::Up));
+        driver.motor = Some(Direction::Up);
+        elevator.requests.add_request(Button::Cab, Floor::from(3));
+
+        arrive_at_floor(&mut driver, &tx, &mut elevator, Floor::from(2)).await;
+
+        assert_eq!(elevator.state, State::Moving(Direction::Up));
+        assert_eq!(driver.motor, Some(Direction::Up));
+        assert_eq!(driver.floor_indicator, Some(Floor::from(2)));
+        assert!(!driver.door_open_light);
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn cab_calls_are_lit_and_hall_calls_are_dispatched() {
+        let (mut elevator, mut driver, tx, mut rx) = setup(0, State::Idle);
+
+        button_press(&mut driver, &tx, &mut elevator, Button::Cab, Floor::from(3)).await;
+        assert!(driver.is_lit(Button::Cab, Floor::from(3)));
+        assert_eq!(
+            elevator.requests.get_cab_requests(),
+            vec![false, false, false, true]
+        );
+
+        let button = Button::Hall(Direction::Down);
+        button_press(&mut driver, &tx, &mut elevator, button, Floor::from(2)).await;
+        assert!(!driver.is_lit(button, Floor::from(2)));
+        let request = Message::Request {
+            floor: Floor::from(2),
+            direction: Direction::Down,
+        };
+        assert_eq!(rx.recv().await.unwrap(), request);
+    }
+
+    #[tokio::test]
+    async fn closes_door_and_continues_after_timeout() {
+        let (mut elevator, mut driver, tx, _rx) = setup(1, State::Still(Direction::Up));
+        driver.door_open_light = true;
+        elevator.timer = Some(Timer::new(Duration::ZERO));
+        elevator.requests.add_request(Button::Cab, Floor::from(3));
+
+        timer_timed_out(&mut driver, &tx, &mut elevator).await;
+
+        assert!(elevator.timer.is_none());
+        assert!(!driver.door_open_light);
+        assert_eq!(elevator.state, State::Moving(Direction::Up));
+        assert_eq!(driver.motor, Some(Direction::Up));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn obstruction_keeps_door_open() {
+        let (mut elevator, mut driver, tx, _rx) = setup(1, State::Still(Direction::Up));
+        driver.door_open_light = true;
+        elevator.timer = Some(Timer::new(Duration::ZERO));
+        elevator.requests.add_request(Button::Cab, Floor::from(3));
+
+        obstruction(&mut elevator, true);
+        timer_timed_out(&mut driver, &tx, &mut elevator).await;
+        assert!(driver.door_open_light);
+        assert_eq!(driver.motor, None);
+        assert!(elevator.timer.is_some());
+        assert!(elevator.is_available());
+
+        tokio::time::advance(elevator.timing.obstruction_timeout).await;
+        timer_timed_out(&mut driver, &tx, &mut elevator).await;
+        assert_eq!(elevator.health(), Health::Obstructed);
+
+        obstruction(&mut elevator, false);
+        assert!(elevator.is_available());
+        elevator.timer = Some(Timer::new(Duration::ZERO));
+        timer_timed_out(&mut driver, &tx, &mut elevator).await;
+        assert!(!driver.door_open_light);
+        assert_eq!(driver.motor, Some(Direction::Up));
+    }
+
+    #[tokio::test]
+    async fn emergency_stop_between_floors_keeps_door_closed() {
+        let (mut elevator, mut driver, _tx, _rx) = setup(1, State::Moving(Direction::Up));
+        driver.motor = Some(Direction::Up);
+        driver.floor = None;
+        elevator.requests.add_request(Button::Cab, Floor::from(3));
+
+        stop_button(&mut driver, &mut elevator, true).await;
+        assert_eq!(elevator.state, State::EmergencyStop);
+        assert_eq!(driver.motor, None);
+        assert!(driver.stop_button_light);
+        assert!(!driver.door_open_light);
+        assert_eq!(elevator.health(), Health::EmergencyStop);
+
+        // Releasing the button does not resume
+        stop_button(&mut driver, &mut elevator, false).await;
+        assert_eq!(elevator.state, State::EmergencyStop);
+        assert!(elevator.requests.get_request(Floor::from(3), Button::Cab));
+    }
+
+    #[tokio::test]
+    async fn emergency_stop_at_floor_opens_door_until_resumed() {
+        let (mut elevator, mut driver, _tx, _rx) = setup(2, State::Still(Direction::Up));
+        driver.door_open_light = true;
+        elevator.timer = Some(Timer::new(Duration::ZERO));
+
+        stop_button(&mut driver, &mut elevator, true).await;
+        assert_eq!(elevator.state, State::EmergencyStop);
+        assert!(elevator.timer.is_none());
+        assert!(driver.door_open_light);
+        assert!(driver.stop_button_light);
+
+        stop_button(&mut driver, &mut elevator, false).await;
+        stop_button(&mut driver, &mut elevator, true).await;
+        assert_eq!(elevator.state, State::Idle);
+        assert_eq!(elevator.floor, Floor::from(2));
+        assert!(!driver.door_open_light);
+        assert!(!driver.stop_button_light);
+        assert!(elevator.is_available());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn motor_fault_until_floor_sensor_changes() {
+        let (mut elevator, mut driver, tx, _rx) = setup(1, State::Idle);
+        elevator.requests.add_request(Button::Cab, Floor::from(3));
+
+        try_move(&mut driver, &tx, &mut elevator).await.unwrap();
+        assert!(elevator.motor_timer.is_some());
+
+        // The car never leaves floor 1
+        tokio::time::advance(elevator.timing.travel_timeout).await;
+        assert!(elevator.motor_timer.unwrap().is_done());
+        motor_fault(&mut driver, &mut elevator, true).await;
+        assert_eq!(elevator.stuck_at, Some(Floor::from(1)));
+        assert_eq!(elevator.health(), Health::MotorFault);
+
+        // Losing the hardware is reported over the motor fault
+        elevator.connected = false;
+        assert_eq!(elevator.health(), Health::Disconnected);
+        elevator.connected = true;
+        assert_eq!(elevator.state, State::Moving(Direction::Up));
+
+        driver.floor = None;
+        motor_fault(&mut driver, &mut elevator, false).await;
+        assert!(elevator.is_available());
+        assert!(!elevator.motor_timer.unwrap().is_done());
+
+        driver.floor = Some(Floor::from(2));
+        arrive_at_floor(&mut driver, &tx, &mut elevator, Floor::from(2)).await;
+        assert!(elevator.motor_timer.is_some());
+        driver.floor = Some(Floor::from(3));
+        arrive_at_floor(&mut driver, &tx, &mut elevator, Floor::from(3)).await;
+        assert!(elevator.motor_timer.is_none());
+    }
+
+    #[tokio::test]
+    async fn hall_light_off_clears_request() {
+        let (mut elevator, mut driver, _tx, _rx) = setup(0, State::Idle);
+        let (floor, button) = (Floor::from(2), Button::Hall(Direction::Up));
+        elevator.requests.add_request(button, floor);
+
+        let msg = Message::HallButtonLight {
+            floor,
+            direction: Direction::Up,
+            on: false,
+        };
+        message_received(&mut driver, &mut elevator, msg)
+            .await
+            .unwrap();
+
+        assert!(!elevator.requests.get_request(floor, button));
+        assert_eq!(driver.order_lights.get(&(button, floor)), Some(&false));
+    }
+
+    #[tokio::test]
+    async fn assigned_hall_requests_replace_the_old_ones() {
+        let (mut elevator, mut driver, tx, _rx) = setup(1, State::Moving(Direction::Up));
+        driver.motor = Some(Direction::Up);
+        let (up, down) = (Button::Hall(Direction::Up), Button::Hall(Direction::Down));
+        elevator.requests.add_request(up, Floor::from(3));
+        elevator.requests.add_request(Button::Cab, Floor::from(0));
+
+        let requests = vec![(Floor::from(0), Direction::Down)];
+        let msg = Message::AssignedHallRequests {
+            assignment: 1,
+            requests,
+        };
+        message_received(&mut driver, &mut elevator, msg)
+            .await
+            .unwrap();
+        assert!(!elevator.requests.get_request(Floor::from(3), up));
+        assert!(elevator.requests.get_request(Floor::from(0), down));
+        assert!(elevator.requests.get_request(Floor::from(0), Button::Cab));
+
+        // Nothing left above, so the car stops at the next floor instead of going on
+        arrive_at_floor(&mut driver, &tx, &mut elevator, Floor::from(2)).await;
+        assert_eq!(elevator.state, State::Idle);
+        assert_eq!(driver.motor, None);
+        assert!(!driver.door_open_light);
+    }
 }
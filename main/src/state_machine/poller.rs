@@ -0,0 +1,184 @@
+use tokio::sync::mpsc::Sender;
+use tokio::time::sleep;
+
+use interface::types::{Button, Floor};
+use interface::{Edge, Edges, HardwareDriver};
+
+use crate::config::Timing;
+
+use super::types::Input;
+
+/// Last reading of every input
+struct Readings {
+    floor: Option<Floor>,
+    buttons: Edges<(Button, Floor)>,
+    stop_button: Edges<()>,
+    obstruction: bool,
+    connected: bool,
+}
+
+/// Polls the inputs of the hardware, and sends an Input on `tx` every time one changes
+///
+/// The readings start out as an idle elevator between floors, so the first poll
+/// reports the floor and every switch that is on. Runs until the receiver is dropped.
+pub async fn run(
+    task_id: usize,
+    mut driver: impl HardwareDriver,
+    tx: Sender<Input>,
+    timing: Timing,
+) {
+    let mut last = Readings {
+        floor: None,
+        buttons: Edges::new(timing.debounce),
+        stop_button: Edges::new(timing.debounce),
+        obstruction: false,
+        connected: true,
+    };
+
+    while !tx.is_closed() {
+        for input in poll(task_id, &mut driver, &mut last).await {
+            if tx.send(input).await.is_err() {
+                return;
+            }
+        }
+        sleep(timing.poll_interval).await;
+    }
+}
+
+/// Reads every input once, and returns the ones that changed since the last poll
+///
+/// Inputs that can not be read are left as they were.
+async fn poll(task_id: usize, driver: &mut impl HardwareDriver, last: &mut Readings) -> Vec<Input> {
+    let mut changes = Vec::new();
+
+    match driver.floor().await {
+        Ok(floor) if floor != last.floor => {
+            last.floor = floor;
+            changes.push(Input::Floor(floor));
+        }
+        Ok(_) => {}
+        // Lost connections are reported by the driver, which reconnects by itself
+        Err(_) if !driver.is_connected() => {}
+        Err(e) => eprintln!("task {task_id}: caught error in driver.floor() => error: {e}"),
+    }
+
+    match driver.obstruction_switch().await {
+        Ok(obstructed) if obstructed != last.obstruction => {
+            last.obstruction = obstructed;
+            changes.push(Input::Obstruction(obstructed));
+        }
+        Ok(_) => {}
+        Err(_) if !driver.is_connected() => {}
+        Err(e) => {
+            eprintln!("task {task_id}: caught error in driver.obstruction_switch() => error: {e}")
+        }
+    }
+
+    match driver.stop_button().await {
+        Ok(pressed) => {
+            if let Some(edge) = last.stop_button.update((), pressed) {
+                changes.push(Input::StopButton(edge == Edge::Pressed));
+            }
+        }
+        Err(_) if !driver.is_connected() => {}
+        Err(e) => eprintln!("task {task_id}: caught error in driver.stop_button() => error: {e}"),
+    }
+
+    let buttons: Vec<(Button, Floor)> = Button::iterator()
+        .flat_map(|button| {
+            (0..Floor::get_n_floors()).map(move |floor| (button, Floor::from(floor)))
+        })
+        .collect();
+    match driver.order_buttons(&buttons).await {
+        Ok(pressed) => {
+            for (&(button, floor), pressed) in buttons.iter().zip(pressed) {
+                if let Some(edge) = last.buttons.update((button, floor), pressed) {
+                    changes.push(Input::Button(button, floor, edge));
+                }
+            }
+        }
+        Err(_) if !driver.is_connected() => {}
+        Err(e) => eprintln!("task {task_id}: caught error in driver.order_buttons() => error: {e}"),
+    }
+
+    if driver.is_connected() != last.connected {
+        last.connected = driver.is_connected();
+        changes.push(Input::Connection(last.connected));
+    }
+
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use tokio::sync::mpsc;
+
+    use interface::types::floor::N_FLOORS;
+    use interface::types::Direction;
+    use interface::{MockDriver, SharedDriver};
+
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn sends_changes_only() {
+        N_FLOORS.get_or_init(|| 4);
+
+        let driver = SharedDriver::new(MockDriver::at_floor(Floor::from(1)));
+        let (tx, mut rx) = mpsc::channel(10);
+        let timing = Timing::default();
+        let poller = tokio::spawn(run(0, driver.clone(), tx, timing));
+
+        assert_eq!(rx.recv().await, Some(Input::Floor(Some(Floor::from(1)))));
+
+        let button = Button::Hall(Direction::Up);
+        driver.lock().await.pressed.insert((button, Floor::from(2)));
+        let pressed = Input::Button(button, Floor::from(2), Edge::Pressed);
+        assert_eq!(rx.recv().await, Some(pressed));
+
+        // Holding the button down is a single press
+        sleep(timing.poll_interval * 5).await;
+        driver.lock().await.pressed.clear();
+        let released = Input::Button(button, Floor::from(2), Edge::Released);
+        assert_eq!(rx.recv().await, Some(released));
+
+        driver.lock().await.obstruction = true;
+        assert_eq!(rx.recv().await, Some(Input::Obstruction(true)));
+
+        driver.lock().await.disconnected = true;
+        assert_eq!(rx.recv().await, Some(Input::Connection(false)));
+        driver.lock().await.disconnected = false;
+        driver.lock().await.floor = None;
+        assert_eq!(rx.recv().await, Some(Input::Floor(None)));
+        assert_eq!(rx.recv().await, Some(Input::Connection(true)));
+
+        drop(rx);
+        poller.await.unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn debounces_buttons() {
+        N_FLOORS.get_or_init(|| 4);
+
+        let mut driver = MockDriver::at_floor(Floor::from(0));
+        let mut last = Readings {
+            floor: Some(Floor::from(0)),
+            buttons: Edges::new(Duration::from_millis(20)),
+            stop_button: Edges::new(Duration::from_millis(20)),
+            obstruction: false,
+            connected: true,
+        };
+
+        driver.stop_button = true;
+        assert!(poll(0, &mut driver, &mut last).await.is_empty());
+        sleep(Duration::from_millis(10)).await;
+        driver.stop_button = false;
+        assert!(poll(0, &mut driver, &mut last).await.is_empty());
+
+        driver.stop_button = true;
+        assert!(poll(0, &mut driver, &mut last).await.is_empty());
+        sleep(Duration::from_millis(20)).await;
+        let changes = poll(0, &mut driver, &mut last).await;
+        assert_eq!(changes, vec![Input::StopButton(true)]);
+    }
+}
@@ -0,0 +1,178 @@
+use std::time::Duration;
+
+use interface::types::{Direction, Floor};
+
+use crate::config::Timing;
+use crate::types::Elevator;
+
+use super::handle::{check_for_stop, check_in_both_directions};
+use super::types::State;
+
+/// What the elevator did in one call to step()
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step {
+    /// Travelled to the next floor, and kept going
+    Pass,
+    /// Travelled to the next floor, and opened the door to serve it in the given direction
+    Arrive(Direction),
+    /// Opened the door, or kept it open, to serve the current floor in the given direction
+    Open(Direction),
+    /// Started moving from the current floor
+    Depart(Direction),
+    /// Closed the door, with nothing more to do in its direction
+    Close,
+}
+
+impl Step {
+    /// Time the step takes, where each floor travelled takes `timing.travel_time` and
+    /// each stop takes `timing.door_open_time`
+    pub fn duration(self, timing: &Timing) -> Duration {
+        match self {
+            Step::Pass => timing.travel_time,
+            Step::Arrive(_) => timing.travel_time + timing.door_open_time,
+            Step::Open(_) => timing.door_open_time,
+            Step::Depart(_) | Step::Close => Duration::ZERO,
+        }
+    }
+}
+
+/// Makes the next decision of the state machine, assuming the door or motor is done
+/// with the current state, and returns None once the elevator is idle without requests
+pub fn step(elevator: &mut Elevator) -> Option<Step> {
+    match elevator.state {
+        // Same as handle::try_move()
+        State::Idle | State::EmergencyStop => {
+            let stop = Direction::iterator()
+                .find_map(|direction| check_for_stop(elevator, direction).ok());
+            if let Some(direction) = stop {
+                elevator.state = State::Still(direction);
+                Some(Step::Open(direction))
+            } else if let Ok(direction) = check_in_both_directions(elevator) {
+                elevator.state = State::Moving(direction);
+                Some(Step::Depart(direction))
+            } else {
+                None
+            }
+        }
+        // Same as handle::arrive_at_floor(), for the next floor
+        State::Moving(direction) => {
+            let floor = next_floor(elevator.floor, direction)?;
+            elevator.floor = floor;
+            if let Ok(direction) = check_for_stop(elevator, direction) {
+                elevator.state = State::Still(direction);
+                Some(Step::Arrive(direction))
+            } else {
+                if !elevator.requests.check_in_direction(floor, direction) {
+                    elevator.state = State::Idle;
+                }
+                Some(Step::Pass)
+            }
+        }
+        // Same as handle::timer_timed_out(), without obstructions
+        State::Still(direction) => {
+            if let Ok(direction) = check_for_stop(elevator, direction) {
+                elevator.state = State::Still(direction);
+                Some(Step::Open(direction))
+            } else if elevator
+                .requests
+                .check_in_direction(elevator.floor, direction)
+            {
+                elevator.state = State::Moving(direction);
+                Some(Step::Depart(direction))
+            } else {
+                elevator.state = State::Idle;
+                Some(Step::Close)
+            }
+        }
+    }
+}
+
+/// Estimates how long the elevator needs to serve all of its requests and become idle
+///
+/// Makes the same decisions as the state machine, see step(). A moving elevator is
+/// assumed to be halfway to the next floor, and an open door to be halfway done.
+pub fn time_to_idle(mut elevator: Elevator) -> Duration {
+    let (timing, state) = (elevator.timing, elevator.state);
+    let mut duration = Duration::ZERO;
+    while let Some(step) = step(&mut elevator) {
+        duration += step.duration(&timing);
+    }
+
+    match state {
+        State::Moving(_) => duration.saturating_sub(timing.travel_time / 2),
+        State::Still(_) => duration + timing.door_open_time / 2,
+        State::Idle | State::EmergencyStop => duration,
+    }
+}
+
+fn next_floor(floor: Floor, direction: Direction) -> Option<Floor> {
+    let floor = usize::from(floor);
+    let next = match direction {
+        Direction::Up => floor + 1,
+        Direction::Down => floor.checked_sub(1)?,
+    };
+    Floor::from_value(next)
+}
+
+#[cfg(test)]
+mod tests {
+    use interface::types::floor::N_FLOORS;
+    use interface::types::Button;
+
+    use super::*;
+
+    fn elevator(floor: usize, state: State, requests: &[(Button, usize)]) -> Elevator {
+        N_FLOORS.get_or_init(|| 4);
+
+        let timing = Timing {
+            door_open_time: Duration::from_secs(3),
+            travel_time: Duration::from_secs(2),
+            ..Timing::default()
+        };
+        let mut elevator = Elevator::new(Floor::from(floor), timing);
+        elevator.state = state;
+        for &(button, floor) in requests {
+            elevator.requests.add_request(button, Floor::from(floor));
+        }
+        elevator
+    }
+
+    fn secs(elevator: Elevator) -> f64 {
+        time_to_idle(elevator).as_secs_f64()
+    }
+
+    #[test]
+    fn idle_without_requests() {
+        assert_eq!(secs(elevator(2, State::Idle, &[])), 0.0);
+        assert_eq!(secs(elevator(2, State::Still(Direction::Up), &[])), 1.5);
+    }
+
+    #[test]
+    fn travels_and_stops_for_each_request() {
+        let requests = [(Button::Cab, 3)];
+        assert_eq!(secs(elevator(0, State::Idle, &requests)), 9.0);
+
+        // Passes floor 1 and 2 on the way up, and comes back down for the hall call
+        let up = Direction::Up;
+        let requests = [(Button::Cab, 3), (Button::Hall(Direction::Down), 1)];
+        assert_eq!(
+            secs(elevator(1, State::Moving(up), &requests)),
+            1.0 + 2.0 + 3.0 + 4.0 + 3.0
+        );
+
+        // Requests at the current floor are served without moving
+        let requests = [(Button::Hall(up), 2)];
+        assert_eq!(secs(elevator(2, State::Idle, &requests)), 3.0);
+        assert_eq!(secs(elevator(2, State::Still(up), &requests)), 4.5);
+    }
+
+    #[test]
+    fn serves_requests_in_direction_first() {
+        let down = Direction::Down;
+        let requests = [(Button::Cab, 0), (Button::Hall(Direction::Up), 3)];
+
+        // Down to floor 0 and then all the way up, instead of the other way around
+        let expected = 1.5 + 4.0 + 3.0 + 6.0 + 3.0;
+        assert_eq!(secs(elevator(2, State::Still(down), &requests)), expected);
+    }
+}
@@ -1,4 +1,7 @@
+use serde::{Deserialize, Serialize};
+
 use interface::types::{Button, Direction, Floor};
+use interface::Edge;
 use crate::types::Message;
 
 pub enum Event {
@@ -6,21 +9,42 @@ pub enum Event {
     TimerTimedOut,
     MessageReceived(Message),
     ButtonPress(Button, Floor),
+    Obstruction(bool),
+    StopButton(bool),
+    /// The car did not reach a floor in time (true), or started moving again (false)
+    MotorFault(bool),
+    /// The connection to the hardware was lost (false) or restored (true)
+    ConnectionChanged(bool),
 }
 
+/// Change of an input of the hardware, sent by the poller
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Input {
+    /// New reading of the floor sensor, None when the car left a floor
+    Floor(Option<Floor>),
+    Button(Button, Floor, Edge),
+    StopButton(bool),
+    Obstruction(bool),
+    /// The connection to the hardware was lost (false) or restored (true)
+    Connection(bool),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum State {
     Idle,
     Moving(Direction),
     Still(Direction),
+    /// Halted by the stop button until it is pressed again
+    EmergencyStop,
 }
 
 impl std::fmt::Display for State {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         let s = match self {
-            State::Idle => format!("State: Idle"),
+            State::Idle => "State: Idle".to_string(),
             State::Moving(dir) => format!("State: Moving ({dir})"),
-            State::Still(dir) => format!("State: Moving ({dir})"),
+            State::Still(dir) => format!("State: Still ({dir})"),
+            State::EmergencyStop => "State: Emergency stop".to_string(),
         };
         write!(f, "{s}")
     }
@@ -0,0 +1,218 @@
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc::{self, Receiver, Sender};
+use tokio::time::{sleep, Instant};
+
+use crate::config::Timing;
+use crate::hardware::Hardware;
+use crate::state_machine;
+use crate::types::elevator::CabStorage;
+use crate::types::{Health, Message};
+
+const MIN_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_BACKOFF: Duration = Duration::from_secs(10);
+/// A state machine that has run this long is considered healthy, and resets the backoff
+const STABLE_RUNTIME: Duration = Duration::from_secs(30);
+
+/// Settings needed to (re)start the state machine of an elevator
+pub struct Supervised<H> {
+    pub task_id: usize,
+    pub hardware: Arc<H>,
+    pub timing: Timing,
+    pub state_dir: PathBuf,
+}
+
+/// Runs the state machine of an elevator, restarting it whenever it fails
+///
+/// Messages for the elevator are received on `rx` and passed on to the running
+/// state machine, so the dispatcher keeps the same transmitter across restarts.
+/// The elevator is reported as Health::Stopped while it is not running, and `rx`
+/// is kept drained in the meantime, see Stopped.
+pub async fn run<H: Hardware>(
+    elevator: Supervised<H>,
+    (tx, mut rx): (Sender<Message>, Receiver<Message>),
+) {
+    let Supervised {
+        task_id,
+        hardware,
+        timing,
+        state_dir,
+    } = elevator;
+    let address = hardware.describe(task_id);
+    let mut backoff = MIN_BACKOFF;
+    let mut stopped = Stopped {
+        task_id,
+        restore: None,
+    };
+
+    loop {
+        let Some(connected) = stopped.drain(hardware.connect(task_id), &tx, &mut rx).await else {
+            return;
+        };
+        let driver = match connected {
+            Ok(driver) => driver,
+            Err(e) => {
+                eprintln!("task {task_id}: Could not connect to {address} => error: {e}");
+                let waited = stopped.drain(wait(task_id, &mut backoff), &tx, &mut rx);
+                if waited.await.is_none() {
+                    return;
+                }
+                continue;
+            }
+        };
+        println!("task {task_id} connected to {address}");
+
+        let (tx_task, rx_task) = mpsc::channel(100);
+        if let Some(msg) = stopped.restore.take() {
+            let _ = tx_task.send(msg).await;
+        }
+        let storage = CabStorage::new(&state_dir, task_id);
+        let channels = (tx.clone(), rx_task);
+        let mut handle = tokio::spawn(async move {
+            state_machine::run(task_id, driver, channels, timing, storage).await
+        });
+        // The state machine reports itself healthy once it has found a floor
+        let started = Instant::now();
+        let crashed = hardware.crashed(task_id);
+        tokio::pin!(crashed);
+
+        let result = loop {
+            tokio::select! {
+                result = &mut handle => break result,
+                _ = &mut crashed => {
+                    handle.abort();
+                    break handle.await;
+                }
+                msg = rx.recv() => match msg {
+                    // A failed send means the state machine has stopped, which is caught above
+                    Some(msg) => { let _ = tx_task.send(msg).await; }
+                    None => {
+                        handle.abort();
+                        return;
+                    }
+                },
+            }
+        };
+
+        let stopping = set_health(&tx, task_id, Health::Stopped);
+        if stopped.drain(stopping, &tx, &mut rx).await.is_none() {
+            return;
+        }
+        match result {
+            Ok(Err(e)) if !e.critical => {
+                println!("task {task_id}: State machine was shut down ({e})");
+                return;
+            }
+            Ok(Err(e)) => eprintln!("task {task_id}: Error occured in state machine: {e}"),
+            Ok(Ok(())) => eprintln!("task {task_id}: State machine stopped unexpectedly"),
+            Err(e) if e.is_cancelled() => eprintln!("task {task_id}: State machine was killed"),
+            Err(e) => eprintln!("task {task_id}: State machine panicked => error: {e}"),
+        }
+
+        if started.elapsed() > STABLE_RUNTIME {
+            backoff = MIN_BACKOFF;
+        }
+        let waited = stopped.drain(wait(task_id, &mut backoff), &tx, &mut rx);
+        if waited.await.is_none() {
+            return;
+        }
+    }
+}
+
+/// Handles the messages for an elevator while its state machine is not running
+///
+/// The dispatcher must never be kept waiting on a stopped elevator, since it serves
+/// every other elevator as well. Hall requests and lights are dropped, as they are
+/// all sent again once the elevator is healthy, and the dispatcher is reminded that
+/// the elevator is stopped when it is given hall requests. Only the latest restored
+/// cab calls are kept, to be passed on once the state machine has started.
+struct Stopped {
+    task_id: usize,
+    restore: Option<Message>,
+}
+
+impl Stopped {
+    /// Runs `future` to completion while draining `rx`, returns None if the elevator
+    /// was shut down in the meantime
+    async fn drain<F: Future>(
+        &mut self,
+        future: F,
+        tx: &Sender<Message>,
+        rx: &mut Receiver<Message>,
+    ) -> Option<F::Output> {
+        tokio::pin!(future);
+        loop {
+            tokio::select! {
+                output = &mut future => return Some(output),
+                msg = rx.recv() => match msg {
+                    Some(Message::Shutdown) | None => return None,
+                    Some(msg @ Message::RestoreCabCalls { .. }) => self.restore = Some(msg),
+                    Some(Message::AssignedHallRequests { .. }) => {
+                        let health = Health::Stopped;
+                        let msg = Message::Health { task_id: self.task_id, health };
+                        // The dispatcher may be waiting on this elevator, so never wait on it
+                        let _ = tx.try_send(msg);
+                    }
+                    Some(_) => {}
+                },
+            }
+        }
+    }
+}
+
+async fn set_health(tx: &Sender<Message>, task_id: usize, health: Health) {
+    let msg = Message::Health { task_id, health };
+    let _ = tx.send(msg).await;
+}
+
+async fn wait(task_id: usize, backoff: &mut Duration) {
+    println!("task {task_id}: Restarting in {} ms", backoff.as_millis());
+    sleep(*backoff).await;
+    *backoff = (*backoff * 2).min(MAX_BACKOFF);
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::net::TcpListener;
+
+    use interface::types::floor::N_FLOORS;
+
+    use super::*;
+    use crate::hardware::TcpHardware;
+
+    #[tokio::test(start_paused = true)]
+    async fn restarts_after_connection_is_lost() {
+        N_FLOORS.get_or_init(|| 4);
+
+        // Accepts every connection, then hangs up before the elevator is initialized,
+        // so that it is never reported healthy
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                drop(stream);
+            }
+        });
+
+        let (tx, mut rx) = mpsc::channel(100);
+        let (tx_mailbox, rx_mailbox) = mpsc::channel(100);
+        let elevator = Supervised {
+            task_id: 0,
+            hardware: Arc::new(TcpHardware::new(vec![address])),
+            timing: Timing::default(),
+            state_dir: std::env::temp_dir().join("elevators-supervisor-test"),
+        };
+        let handle = tokio::spawn(run(elevator, (tx, rx_mailbox)));
+
+        for _ in 0..2 {
+            let msg = rx.recv().await.unwrap();
+            let health = Health::Stopped;
+            assert_eq!(msg, Message::Health { task_id: 0, health });
+        }
+
+        drop(tx_mailbox);
+        handle.await.unwrap();
+    }
+}
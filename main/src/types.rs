@@ -1,10 +1,17 @@
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
 use tokio::sync::mpsc::Sender;
+use tokio::time::Instant;
 
 use interface::types::{Direction, Floor};
 
+use crate::config::Timing;
 use crate::state_machine::types::State;
 
 pub mod elevator;
+pub mod hall_requests;
+pub mod health;
 pub mod task_info;
 
 use self::elevator::{Requests, Timer};
@@ -14,14 +21,40 @@ pub struct Elevator {
     pub state: State,
     pub requests: Requests,
     pub timer: Option<Timer>,
+    pub timing: Timing,
+    /// Whether the hardware could be reached the last time it was checked
+    pub connected: bool,
+    pub obstructed: bool,
+    /// Whether the stop button was held down the last time it was checked
+    pub stop_button: bool,
+    /// Set when the door should have closed, but was kept open by an obstruction
+    pub door_blocked_since: Option<Instant>,
+    /// Runs while the motor is on, the car should reach a floor before it is done
+    pub motor_timer: Option<Timer>,
+    /// Set when the car did not reach a floor in time, until the floor sensor
+    /// reading changes from `stuck_at`
+    pub motor_fault: bool,
+    pub stuck_at: Option<Floor>,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Message {
     Request {
         floor: Floor,
         direction: Direction,
     },
+    /// Every hall request given to an elevator, replacing the ones it was given before
+    ///
+    /// The elevator answers with HallRequestsAccepted, with the same `assignment`.
+    AssignedHallRequests {
+        assignment: u64,
+        requests: Vec<(Floor, Direction)>,
+    },
+    /// Sent by an elevator once it has taken over the requests of an assignment
+    HallRequestsAccepted {
+        task_id: usize,
+        assignment: u64,
+    },
     HallButtonLight {
         floor: Floor,
         direction: Direction,
@@ -31,7 +64,26 @@ pub enum Message {
         task_id: usize,
         floor: Floor,
         state: State,
-        n_requests: usize,
+        requests: Requests,
+    },
+    /// Sent when an elevator stops or starts being able to serve requests, see Health
+    Health {
+        task_id: usize,
+        health: Health,
+    },
+    /// Cab calls of an elevator, backed up by the other elevators
+    ///
+    /// `session` is different every time the state machine starts, so that a backup
+    /// is not replaced by the calls of an elevator that has just lost them.
+    CabCalls {
+        task_id: usize,
+        session: u64,
+        calls: Vec<bool>,
+    },
+    /// Backed up cab calls, sent to an elevator when it has restarted
+    RestoreCabCalls {
+        task_id: usize,
+        calls: Vec<bool>,
     },
     Shutdown,
 }
@@ -41,5 +93,62 @@ pub struct TaskInfo {
     pub transmitter: Sender<Message>,
     pub floor: Floor,
     pub state: State,
-    pub n_requests: usize,
-}
\ No newline at end of file
+    /// Every request the elevator reported, including the hall requests it was given
+    pub requests: Requests,
+    /// Reported by the elevator itself, or its supervisor
+    pub health: Health,
+    /// Whether the elevator can be reached over the network, always true for local elevators
+    pub connected: bool,
+}
+
+/// Whether an elevator can serve requests, and otherwise why not
+///
+/// The hall requests of an elevator that is not healthy are given to other elevators.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Health {
+    #[default]
+    Healthy,
+    /// The door has been kept open by an obstruction for longer than the obstruction timeout
+    Obstructed,
+    /// The motor ran without reaching a floor in time
+    MotorFault,
+    /// The hardware of the elevator can not be reached
+    Disconnected,
+    /// Halted by the stop button
+    EmergencyStop,
+    /// The state machine is not running, e.g. while it is being restarted
+    Stopped,
+    /// The elevator did not accept its hall requests in time
+    Unresponsive,
+}
+
+/// Hall requests of every floor and direction, replicated on every node
+///
+/// Each node keeps its own copy and broadcasts it periodically. Copies are
+/// merged entry by entry with HallRequests::merge(), where the highest version wins.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HallRequests {
+    orders: Vec<[HallOrder; 2]>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HallOrder {
+    pub state: OrderState,
+    pub version: u64,
+    pub seen_by: BTreeSet<usize>,
+}
+
+/// Lifecycle of a hall request: Unconfirmed -> Confirmed -> Assigned -> Served
+///
+/// Served is also the state of a hall button that has never been pressed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum OrderState {
+    /// Pressed, but not yet seen by every connected node
+    Unconfirmed,
+    /// Seen by every connected node, the hall light is turned on
+    Confirmed,
+    /// Given to the elevator `owner`
+    Assigned { owner: usize },
+    /// No active request
+    Served,
+}
@@ -1,23 +1,40 @@
 use std::collections::HashMap;
-use std::time::{Duration, Instant};
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::time::Instant;
+
+use serde::{Deserialize, Serialize};
 
 use interface::types::{Button, Floor};
 
+use crate::config::Timing;
 use crate::error::ElevatorError;
 use crate::state_machine::types::State;
 
 pub mod requests;
+pub mod storage;
 pub mod timer;
 
 use self::requests::Array;
-use super::Elevator;
+use super::{Elevator, Health};
 
+/// Every request of an elevator, sent over the network as one row per floor with the
+/// hall up, hall down and cab request in that order
+///
+/// A matrix is only accepted with one row for each floor of Floor::get_n_floors().
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(into = "Vec<[bool; 3]>", try_from = "Vec<[bool; 3]>")]
 pub struct Requests {
     map: HashMap<Button, Array<bool>>,
-    active_buttons: HashMap<Button, Array<bool>>,
     n_floors: usize,
 }
 
+/// Snapshot of the cab calls on disk, so they survive crashes and power loss
+pub struct CabStorage {
+    path: PathBuf,
+    saved: Vec<bool>,
+}
+
 #[derive(Debug, Copy, Clone)]
 pub struct Timer {
     now: Instant,
@@ -25,15 +42,46 @@ pub struct Timer {
 }
 
 impl Elevator {
-    pub fn new(floor: Floor) -> Elevator {
+    pub fn new(floor: Floor, timing: Timing) -> Elevator {
         Elevator {
             floor,
             state: State::Idle,
             requests: Requests::new(Floor::get_n_floors()),
             timer: None,
+            timing,
+            connected: true,
+            obstructed: false,
+            stop_button: false,
+            door_blocked_since: None,
+            motor_timer: None,
+            motor_fault: false,
+            stuck_at: None,
+        }
+    }
+
+    /// Whether the elevator can serve requests, otherwise its hall requests should
+    /// be given to other elevators
+    pub fn health(&self) -> Health {
+        let blocked = self
+            .door_blocked_since
+            .is_some_and(|since| since.elapsed() >= self.timing.obstruction_timeout);
+        if !self.connected {
+            Health::Disconnected
+        } else if self.state == State::EmergencyStop {
+            Health::EmergencyStop
+        } else if self.motor_fault {
+            Health::MotorFault
+        } else if blocked {
+            Health::Obstructed
+        } else {
+            Health::Healthy
         }
     }
 
+    pub fn is_available(&self) -> bool {
+        self.health().is_available()
+    }
+
     pub fn error(&self, critical: bool) -> ElevatorError {
         let (floor, state) = (self.floor, self.state);
         ElevatorError {
@@ -48,7 +96,7 @@ impl std::fmt::Display for Elevator {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         let s1 = format!("elevator on floor {} in state {}", self.floor, self.state);
         let s2 = match self.state {
-            State::Idle => String::new(),
+            State::Idle | State::EmergencyStop => String::new(),
             State::Moving(dir) | State::Still(dir) => format!("with direction {}", dir),
         };
         write!(f, "{} {}", s1, s2)
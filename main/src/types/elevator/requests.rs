@@ -4,6 +4,7 @@ use interface::types::{Button, Direction, Floor};
 
 use super::Requests;
 
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Array<T: Copy> {
     arr: Box<[T]>,
     len: usize,
@@ -31,8 +32,8 @@ impl<T: Copy> Array<T> {
         self.arr[index] = val;
     }
 
-    pub fn iter(&self) -> core::slice::Iter<T> {
-        self.arr.into_iter()
+    pub fn iter(&self) -> core::slice::Iter<'_, T> {
+        self.arr.iter()
     }
 
     pub fn len(&self) -> usize {
@@ -43,17 +44,11 @@ impl<T: Copy> Array<T> {
 impl Requests {
     pub fn new(n_floors: usize) -> Requests {
         let mut map = HashMap::new();
-        let mut active_buttons = HashMap::new();
         for button in Button::iterator() {
             map.insert(button, Array::from_val(false, n_floors));
-            active_buttons.insert(button, Array::from_val(true, n_floors));
         }
 
-        Requests {
-            map,
-            active_buttons,
-            n_floors,
-        }
+        Requests { map, n_floors }
     }
 
     /*
@@ -70,6 +65,17 @@ impl Requests {
         self.map.get_mut(&button).unwrap().set(true, floor.into());
     }
 
+    /// Replaces every hall request with the given ones, leaving the cab calls as they are
+    pub fn set_hall_requests(&mut self, requests: &[(Floor, Direction)]) {
+        for direction in Direction::iterator() {
+            let hall = self.map.get_mut(&Button::Hall(direction)).unwrap();
+            *hall = Array::from_val(false, self.n_floors);
+        }
+        for &(floor, direction) in requests {
+            self.add_request(Button::Hall(direction), floor);
+        }
+    }
+
     pub fn get_request(&mut self, floor: Floor, button: Button) -> bool {
         let index = usize::from(floor);
         let check = self.map.get(&button).unwrap().get(index);
@@ -81,7 +87,11 @@ impl Requests {
         check
     }
 
-    pub fn get_requests_at_floor(&mut self, current_floor: Floor, direction: Direction) -> Vec<Button> {
+    pub fn get_requests_at_floor(
+        &mut self,
+        current_floor: Floor,
+        direction: Direction,
+    ) -> Vec<Button> {
         let mut results = Vec::new();
         let buttons = [Button::Cab, Button::Hall(direction)];
         let index = usize::from(current_floor);
@@ -96,14 +106,17 @@ impl Requests {
         results
     }
 
+    /// Checks for requests of any kind beyond the current floor in the given direction
+    ///
+    /// Hall requests in the opposite direction count as well, since the elevator has
+    /// to travel there to serve them.
     pub fn check_in_direction(&self, current_floor: Floor, direction: Direction) -> bool {
-        let buttons = [Button::Cab, Button::Hall(direction)];
         let floors = match direction {
             Direction::Up => (usize::from(current_floor) + 1)..self.n_floors,
             Direction::Down => 0..usize::from(current_floor),
         };
 
-        for button in buttons {
+        for button in Button::iterator() {
             if self.map.get(&button).unwrap().arr[floors.clone()]
                 .iter()
                 .any(|&x| x)
@@ -134,29 +147,61 @@ impl Requests {
                 .get(&button)
                 .unwrap()
                 .iter()
-                .filter(|&x| *x == true)
+                .filter(|&x| *x)
                 .collect::<Vec<_>>()
                 .len();
         }
         n_requests
     }
 
-    pub fn get_active_buttons(&self, button: Button) -> Vec<Floor> {
-        self.active_buttons
-            .get(&button)
+    pub fn get_cab_requests(&self) -> Vec<bool> {
+        self.map
+            .get(&Button::Cab)
             .unwrap()
             .iter()
-            .enumerate()
-            .filter(|&x| *x.1 == true)
-            .map(|x| Floor::from(x.0))
-            .collect::<Vec<Floor>>()
+            .copied()
+            .collect()
     }
 
-    pub fn update_active_button(&mut self, button: Button, floor: Floor, active: bool) {
-        self.active_buttons
-            .get_mut(&button)
-            .unwrap()
-            .set(active, floor.into());
+    /// Whether there is a request of the given kind at the floor
+    pub fn has_request(&self, button: Button, floor: Floor) -> bool {
+        self.map.get(&button).unwrap().get(floor.into())
+    }
+}
+
+const MATRIX_COLUMNS: [Button; 3] = [
+    Button::Hall(Direction::Up),
+    Button::Hall(Direction::Down),
+    Button::Cab,
+];
+
+impl From<Requests> for Vec<[bool; 3]> {
+    fn from(requests: Requests) -> Vec<[bool; 3]> {
+        (0..requests.n_floors)
+            .map(|floor| MATRIX_COLUMNS.map(|button| requests.map[&button].get(floor)))
+            .collect()
+    }
+}
+
+impl TryFrom<Vec<[bool; 3]>> for Requests {
+    type Error = String;
+
+    fn try_from(matrix: Vec<[bool; 3]>) -> Result<Requests, String> {
+        let n_floors = Floor::get_n_floors();
+        if matrix.len() != n_floors {
+            let len = matrix.len();
+            return Err(format!(
+                "got requests for {len} floors, expected {n_floors}"
+            ));
+        }
+
+        let mut requests = Requests::new(n_floors);
+        for (floor, row) in matrix.iter().enumerate() {
+            for (button, &requested) in MATRIX_COLUMNS.iter().zip(row) {
+                requests.map.get_mut(button).unwrap().set(requested, floor);
+            }
+        }
+        Ok(requests)
     }
 }
 
@@ -166,3 +211,32 @@ pub enum Status {
     Available,
 }
 */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_request_beyond_the_floor_is_in_direction() {
+        let mut requests = Requests::new(4);
+        assert!(!requests.check_in_direction(Floor::from(1), Direction::Up));
+
+        // The car has to travel up to serve a call going down from above
+        requests.add_request(Button::Hall(Direction::Down), Floor::from(3));
+        assert!(requests.check_in_direction(Floor::from(1), Direction::Up));
+        assert!(!requests.check_in_direction(Floor::from(1), Direction::Down));
+        assert!(!requests.check_in_direction(Floor::from(3), Direction::Up));
+
+        let mut requests = Requests::new(4);
+        requests.add_request(Button::Cab, Floor::from(0));
+        assert!(requests.check_in_direction(Floor::from(2), Direction::Down));
+        assert!(!requests.check_in_direction(Floor::from(2), Direction::Up));
+
+        // Requests at the current floor are not ahead in either direction
+        let mut requests = Requests::new(4);
+        requests.add_request(Button::Hall(Direction::Up), Floor::from(2));
+        requests.add_request(Button::Cab, Floor::from(2));
+        assert!(!requests.check_in_direction(Floor::from(2), Direction::Up));
+        assert!(!requests.check_in_direction(Floor::from(2), Direction::Down));
+    }
+}
@@ -0,0 +1,159 @@
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+
+use super::CabStorage;
+
+const HEADER: &str = "cab";
+
+impl CabStorage {
+    pub fn new(dir: &Path, task_id: usize) -> CabStorage {
+        CabStorage {
+            path: dir.join(format!("cab_calls_{task_id}.txt")),
+            saved: Vec::new(),
+        }
+    }
+
+    /// Reads the stored cab calls, or no calls if there is no valid snapshot
+    pub fn load(&mut self, n_floors: usize) -> Vec<bool> {
+        let calls = match fs::read_to_string(&self.path) {
+            Ok(contents) => decode(&contents, n_floors).unwrap_or_else(|e| {
+                eprintln!("Ignoring cab calls in {}: {e}", self.path.display());
+                vec![false; n_floors]
+            }),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => vec![false; n_floors],
+            Err(e) => {
+                eprintln!("Could not read {} => error: {e}", self.path.display());
+                vec![false; n_floors]
+            }
+        };
+        self.saved = calls.clone();
+        calls
+    }
+
+    /// Stores the cab calls if they have changed since the last save
+    ///
+    /// The snapshot is written to a temporary file that is synced to disk and
+    /// then renamed over the old one, so a crash leaves either the old or the new snapshot.
+    /// The directory is synced as well, so that the rename survives a power loss.
+    pub fn save(&mut self, calls: &[bool]) -> io::Result<()> {
+        if self.saved == calls {
+            return Ok(());
+        }
+
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let tmp = self.path.with_extension("tmp");
+        let mut file = File::create(&tmp)?;
+        file.write_all(encode(calls).as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&tmp, &self.path)?;
+        sync_dir(&self.path)?;
+
+        self.saved = calls.to_vec();
+        Ok(())
+    }
+}
+
+/// Syncs the directory holding the file, which is where a rename is recorded
+#[cfg(unix)]
+fn sync_dir(path: &Path) -> io::Result<()> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    File::open(dir)?.sync_all()
+}
+
+/// Directories can not be opened as files on other platforms
+#[cfg(not(unix))]
+fn sync_dir(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+/// Formats the calls as a single line: "cab <n_floors> <calls> <checksum>", e.g. "cab 4 0100 9f3a2c1b"
+fn encode(calls: &[bool]) -> String {
+    let bits: String = calls
+        .iter()
+        .map(|&call| if call { '1' } else { '0' })
+        .collect();
+    let body = format!("{HEADER} {} {bits}", calls.len());
+    format!("{body} {:08x}\n", checksum(&body))
+}
+
+fn decode(contents: &str, n_floors: usize) -> Result<Vec<bool>, String> {
+    let line = contents
+        .strip_suffix('\n')
+        .ok_or("snapshot is incomplete")?;
+    let (body, sum) = line.rsplit_once(' ').ok_or("missing checksum")?;
+    if u32::from_str_radix(sum, 16) != Ok(checksum(body)) {
+        return Err("checksum does not match".into());
+    }
+
+    let mut fields = body.split(' ');
+    if fields.next() != Some(HEADER) {
+        return Err("unknown format".into());
+    }
+    let len: usize = fields
+        .next()
+        .and_then(|len| len.parse().ok())
+        .ok_or("missing number of floors")?;
+    if len != n_floors {
+        return Err(format!("stored for {len} floors, expected {n_floors}"));
+    }
+
+    let bits = fields.next().unwrap_or_default();
+    if bits.len() != len || fields.next().is_some() {
+        return Err("malformed calls".into());
+    }
+    bits.chars()
+        .map(|c| match c {
+            '0' => Ok(false),
+            '1' => Ok(true),
+            _ => Err(format!("invalid call '{c}'")),
+        })
+        .collect()
+}
+
+/// FNV-1a hash, used to detect torn or corrupted snapshots
+fn checksum(body: &str) -> u32 {
+    body.bytes().fold(0x811c9dc5, |hash, byte| {
+        (hash ^ byte as u32).wrapping_mul(0x01000193)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_and_decode() {
+        let calls = vec![false, true, false, true];
+        let contents = encode(&calls);
+        assert!(contents.starts_with("cab 4 0101 "));
+        assert_eq!(decode(&contents, 4), Ok(calls));
+
+        assert!(decode(&contents, 6).is_err());
+        assert!(decode(&contents[..contents.len() - 3], 4).is_err());
+        assert!(decode(&contents.replace("0101", "0111"), 4).is_err());
+        assert!(decode("", 4).is_err());
+    }
+
+    #[test]
+    fn save_and_load() {
+        let dir = std::env::temp_dir().join(format!("elevators-storage-{}", std::process::id()));
+        let mut storage = CabStorage::new(&dir, 3);
+        assert_eq!(storage.load(4), vec![false; 4]);
+
+        storage.save(&[true, false, false, true]).unwrap();
+        let mut restarted = CabStorage::new(&dir, 3);
+        assert_eq!(restarted.load(4), vec![true, false, false, true]);
+
+        // A torn write is ignored instead of restoring garbage
+        fs::write(&restarted.path, "cab 4 10").unwrap();
+        assert_eq!(restarted.load(4), vec![false; 4]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
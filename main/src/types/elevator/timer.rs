@@ -1,16 +1,25 @@
-use std::time::{Duration, Instant};
+use std::time::Duration;
+use tokio::time::Instant;
 
 use super::Timer;
 
 impl Timer {
-    pub fn from_secs(secs: u64) -> Timer {
+    pub fn new(duration: Duration) -> Timer {
         Timer {
             now: Instant::now(),
-            duration: Duration::from_secs(secs),
+            duration,
         }
     }
 
+    pub fn from_secs(secs: u64) -> Timer {
+        Timer::new(Duration::from_secs(secs))
+    }
+
     pub fn is_done(&self) -> bool {
         self.now.elapsed() >= self.duration
     }
+
+    pub fn deadline(&self) -> Instant {
+        self.now + self.duration
+    }
 }
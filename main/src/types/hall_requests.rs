@@ -0,0 +1,199 @@
+use std::cmp::Ordering;
+use std::collections::BTreeSet;
+
+use interface::types::{Direction, Floor};
+
+use super::{HallOrder, HallRequests, OrderState};
+
+impl HallRequests {
+    pub fn new(n_floors: usize) -> HallRequests {
+        HallRequests {
+            orders: (0..n_floors)
+                .map(|_| [HallOrder::new(), HallOrder::new()])
+                .collect(),
+        }
+    }
+
+    pub fn get(&self, floor: Floor, direction: Direction) -> &HallOrder {
+        &self.orders[usize::from(floor)][index(direction)]
+    }
+
+    fn get_mut(&mut self, floor: Floor, direction: Direction) -> &mut HallOrder {
+        &mut self.orders[usize::from(floor)][index(direction)]
+    }
+
+    /// Iterates over every order as (floor, direction, order)
+    pub fn iter(&self) -> impl Iterator<Item = (Floor, Direction, &HallOrder)> {
+        self.orders.iter().enumerate().flat_map(|(floor, orders)| {
+            Direction::iterator()
+                .map(move |direction| (Floor::from(floor), direction, &orders[index(direction)]))
+        })
+    }
+
+    /// Registers a button press at this node, returns true if the table changed
+    pub fn press(&mut self, floor: Floor, direction: Direction, node: usize) -> bool {
+        let order = self.get_mut(floor, direction);
+        if order.state != OrderState::Served {
+            return false;
+        }
+        order.advance(OrderState::Unconfirmed, node);
+        true
+    }
+
+    /// Marks a request as served, returns true if the table changed
+    pub fn serve(&mut self, floor: Floor, direction: Direction, node: usize) -> bool {
+        let order = self.get_mut(floor, direction);
+        if order.state == OrderState::Served {
+            return false;
+        }
+        order.advance(OrderState::Served, node);
+        true
+    }
+
+    /// Gives a confirmed or assigned request to a new owner
+    pub fn assign(&mut self, floor: Floor, direction: Direction, owner: usize, node: usize) {
+        self.get_mut(floor, direction)
+            .advance(OrderState::Assigned { owner }, node);
+    }
+
+    /// Confirms every unconfirmed request that has been seen by all the given nodes
+    pub fn confirm(&mut self, nodes: &[usize], node: usize) -> bool {
+        let mut changed = false;
+        for order in self.orders.iter_mut().flatten() {
+            if order.state == OrderState::Unconfirmed
+                && nodes.iter().all(|id| order.seen_by.contains(id))
+            {
+                order.advance(OrderState::Confirmed, node);
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    /// Merges the table of another node into this one, returns true if the table changed
+    pub fn merge(&mut self, other: &HallRequests, node: usize) -> bool {
+        if other.orders.len() != self.orders.len() {
+            eprintln!("Received hall requests for {} floors", other.orders.len());
+            return false;
+        }
+
+        let mut changed = false;
+        let orders = self.orders.iter_mut().flatten();
+        for (order, other) in orders.zip(other.orders.iter().flatten()) {
+            changed |= order.merge(other, node);
+        }
+        changed
+    }
+}
+
+impl HallOrder {
+    fn new() -> HallOrder {
+        HallOrder {
+            state: OrderState::Served,
+            version: 0,
+            seen_by: BTreeSet::new(),
+        }
+    }
+
+    /// Checks if the hall button light should be on for this order
+    pub fn is_active(&self) -> bool {
+        matches!(
+            self.state,
+            OrderState::Confirmed | OrderState::Assigned { .. }
+        )
+    }
+
+    fn advance(&mut self, state: OrderState, node: usize) {
+        self.state = state;
+        self.version += 1;
+        self.seen_by = BTreeSet::from([node]);
+    }
+
+    /// The newest version wins. If two nodes changed the same version at the same time,
+    /// the state furthest in the lifecycle wins, and then the highest owner.
+    fn merge(&mut self, other: &HallOrder, node: usize) -> bool {
+        match (self.version, self.state).cmp(&(other.version, other.state)) {
+            Ordering::Less => {
+                *self = other.clone();
+                self.seen_by.insert(node);
+                true
+            }
+            Ordering::Equal => {
+                let n_seen = self.seen_by.len();
+                self.seen_by.extend(other.seen_by.iter());
+                self.seen_by.len() != n_seen
+            }
+            Ordering::Greater => false,
+        }
+    }
+}
+
+fn index(direction: Direction) -> usize {
+    match direction {
+        Direction::Up => 0,
+        Direction::Down => 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use interface::types::floor::N_FLOORS;
+
+    use super::*;
+
+    const UP: Direction = Direction::Up;
+
+    fn floor(val: usize) -> Floor {
+        N_FLOORS.get_or_init(|| 4);
+        Floor::from(val)
+    }
+
+    #[test]
+    fn lifecycle_across_nodes() {
+        let (mut a, mut b) = (HallRequests::new(4), HallRequests::new(4));
+
+        assert!(a.press(floor(2), UP, 0));
+        assert!(!a.press(floor(2), UP, 0));
+        assert!(!a.confirm(&[0, 1], 0));
+
+        assert!(b.merge(&a, 1));
+        assert!(b.confirm(&[0, 1], 1));
+        assert!(b.get(floor(2), UP).is_active());
+
+        assert!(a.merge(&b, 0));
+        assert_eq!(a.get(floor(2), UP).state, OrderState::Confirmed);
+
+        a.assign(floor(2), UP, 1, 0);
+        b.merge(&a, 1);
+        assert_eq!(b.get(floor(2), UP).state, OrderState::Assigned { owner: 1 });
+
+        assert!(b.serve(floor(2), UP, 1));
+        a.merge(&b, 0);
+        assert!(!a.get(floor(2), UP).is_active());
+        assert!(!a.merge(&b, 0));
+    }
+
+    #[test]
+    fn concurrent_changes_converge() {
+        let mut a = HallRequests::new(4);
+        a.press(floor(1), UP, 0);
+        a.confirm(&[0], 0);
+        a.assign(floor(1), UP, 0, 0);
+        let mut b = a.clone();
+
+        // a serves the request while b reassigns it
+        a.serve(floor(1), UP, 0);
+        b.assign(floor(1), UP, 1, 1);
+
+        a.merge(&b, 0);
+        b.merge(&a, 1);
+        assert_eq!(a.get(floor(1), UP).state, OrderState::Served);
+        assert_eq!(b.get(floor(1), UP).state, OrderState::Served);
+
+        // Both nodes register the same press before hearing from each other
+        a.press(floor(3), Direction::Down, 0);
+        b.press(floor(3), Direction::Down, 1);
+        a.merge(&b, 0);
+        assert!(a.confirm(&[0, 1], 0));
+    }
+}
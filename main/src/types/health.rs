@@ -0,0 +1,22 @@
+use super::Health;
+
+impl Health {
+    pub fn is_available(self) -> bool {
+        self == Health::Healthy
+    }
+}
+
+impl std::fmt::Display for Health {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let s = match self {
+            Health::Healthy => "available",
+            Health::Obstructed => "unavailable (obstructed)",
+            Health::MotorFault => "unavailable (motor fault)",
+            Health::Disconnected => "unavailable (disconnected)",
+            Health::EmergencyStop => "unavailable (emergency stop)",
+            Health::Stopped => "unavailable (not running)",
+            Health::Unresponsive => "unavailable (not responding)",
+        };
+        write!(f, "{s}")
+    }
+}
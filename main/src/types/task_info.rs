@@ -1,9 +1,13 @@
+use std::time::Duration;
 use tokio::sync::mpsc::Sender;
 
-use interface::types::{Direction, Floor};
+use interface::types::{Button, Direction, Floor};
 
+use crate::config::{CostWeights, Timing};
+use crate::state_machine::simulate;
 use crate::state_machine::types::State;
-use crate::types::{Message, TaskInfo};
+use crate::types::elevator::Requests;
+use crate::types::{Elevator, Health, Message, TaskInfo};
 
 impl TaskInfo {
     pub fn new(
@@ -15,32 +19,336 @@ impl TaskInfo {
             transmitter,
             floor: Floor::new(),
             state: State::Idle,
-            n_requests: 0,
+            requests: Requests::new(Floor::get_n_floors()),
+            health: Health::Healthy,
+            connected: true,
         }
     }
 
-    pub fn cost_function(&self, floor: Floor, direction: Direction) -> usize {
-        let _in_direction = match self.state {
+    pub fn is_available(&self) -> bool {
+        self.health.is_available() && self.connected
+    }
+
+    /// Cab calls of the elevator, which it serves no matter who is given the hall requests
+    pub fn cab_requests(&self) -> Requests {
+        let mut requests = self.requests.clone();
+        requests.set_hall_requests(&[]);
+        requests
+    }
+
+    /// Whether the elevator already stops at the floor further along its way, for a cab
+    /// call or a hall request in the same direction
+    pub fn stops_on_the_way(&self, floor: Floor, direction: Direction) -> bool {
+        self.stops_on_the_way_with(&self.requests, floor, direction)
+    }
+
+    fn stops_on_the_way_with(
+        &self,
+        requests: &Requests,
+        floor: Floor,
+        direction: Direction,
+    ) -> bool {
+        let (here, there) = (usize::from(self.floor), usize::from(floor));
+        let ahead = match self.state {
+            State::Moving(dir) | State::Still(dir) if dir == direction => match dir {
+                Direction::Up => there > here,
+                Direction::Down => there < here,
+            },
+            _ => false,
+        };
+        ahead
+            && (requests.has_request(Button::Cab, floor)
+                || requests.has_request(Button::Hall(direction), floor))
+    }
+
+    /// Estimated time until the elevator is done with the given requests, starting
+    /// from its last reported floor and state
+    pub fn time_to_idle(&self, requests: Requests, timing: Timing) -> Duration {
+        let mut elevator = Elevator::new(self.floor, timing);
+        elevator.state = self.state;
+        elevator.requests = requests;
+        simulate::time_to_idle(elevator)
+    }
+
+    /// Cost of giving the elevator a hall request, which is zero when the elevator
+    /// already stops at the floor on its way
+    pub fn cost_function(
+        &self,
+        floor: Floor,
+        direction: Direction,
+        weights: &CostWeights,
+    ) -> usize {
+        self.cost_function_with(&self.requests, floor, direction, weights)
+    }
+
+    /// Same as cost_function(), for the elevator with the given requests instead of
+    /// the ones it reported
+    pub fn cost_function_with(
+        &self,
+        requests: &Requests,
+        floor: Floor,
+        direction: Direction,
+        weights: &CostWeights,
+    ) -> usize {
+        if self.stops_on_the_way_with(requests, floor, direction) {
+            return 0;
+        }
+
+        let in_direction = match self.state {
             State::Idle => true,
             State::Moving(dir) => direction == dir,
             State::Still(dir) => direction == dir,
+            State::EmergencyStop => false,
         };
         let floor_difference = usize::from(floor).abs_diff(usize::from(self.floor));
-        //Self::cost_function_helper(self.state, floor_difference, self.n_requests, in_direction)
-        floor_difference
+        Self::cost_function_helper(
+            weights,
+            self.state,
+            floor_difference,
+            requests.number_of_requests(),
+            in_direction,
+        )
     }
 
     fn cost_function_helper(
+        weights: &CostWeights,
         state: State,
         floor_difference: usize,
         n_requests: usize,
         in_direction: bool,
     ) -> usize {
         let state_value = match state {
-            State::Idle => 0,
-            State::Moving(..) => 1,
-            State::Still(..) => 3,
+            State::Idle => weights.idle,
+            State::Moving(..) => weights.moving,
+            State::Still(..) | State::EmergencyStop => weights.still,
+        };
+        state_value
+            + weights.distance * floor_difference
+            + weights.requests * n_requests
+            + weights.wrong_direction * (!in_direction as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::mpsc;
+
+    use interface::types::floor::N_FLOORS;
+    use interface::types::Button;
+
+    use super::*;
+
+    /// Linear congruential generator, so that every run sees the same situations
+    struct Rng(u64);
+
+    impl Rng {
+        fn below(&mut self, n: usize) -> usize {
+            self.0 = self
+                .0
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (self.0 >> 33) as usize % n
+        }
+    }
+
+    struct Situation {
+        elevators: Vec<(TaskInfo, Vec<(Button, Floor)>)>,
+        call: (Floor, Direction),
+    }
+
+    impl Situation {
+        /// Elevators at random floors in random states, each with a few requests
+        fn random(rng: &mut Rng, n_elevators: usize) -> Situation {
+            let n_floors = *N_FLOORS.get_or_init(|| 4);
+            let direction = |rng: &mut Rng, floor: usize| match floor {
+                0 => Direction::Up,
+                f if f == n_floors - 1 => Direction::Down,
+                _ => [Direction::Up, Direction::Down][rng.below(2)],
+            };
+
+            let elevators = (0..n_elevators)
+                .map(|id| {
+                    let mut task = TaskInfo::new(id, mpsc::channel(1).0);
+                    let floor = rng.below(n_floors);
+                    task.floor = Floor::from(floor);
+                    task.state = match rng.below(3) {
+                        0 => State::Idle,
+                        1 => State::Moving(direction(rng, floor)),
+                        _ => State::Still(direction(rng, floor)),
+                    };
+                    let requests: Vec<_> = Button::iterator()
+                        .flat_map(|button| (0..n_floors).map(move |f| (button, Floor::from(f))))
+                        .filter(|_| rng.below(6) == 0)
+                        .collect();
+                    for &(button, floor) in &requests {
+                        task.requests.add_request(button, floor);
+                    }
+                    (task, requests)
+                })
+                .collect();
+
+            let floor = rng.below(n_floors);
+            let call = (Floor::from(floor), direction(rng, floor));
+            Situation { elevators, call }
+        }
+
+        fn requests(&self, id: usize, with_call: bool) -> Requests {
+            let mut requests = Requests::new(Floor::get_n_floors());
+            for &(button, floor) in self.elevators[id].1.iter() {
+                requests.add_request(button, floor);
+            }
+            if with_call {
+                let (floor, direction) = self.call;
+                requests.add_request(Button::Hall(direction), floor);
+            }
+            requests
+        }
+
+        fn time_to_idle(&self, id: usize, with_call: bool, timing: Timing) -> Duration {
+            self.elevators[id]
+                .0
+                .time_to_idle(self.requests(id, with_call), timing)
+        }
+
+        /// Elevator picked by the cost function
+        fn cost_function_choice(&self, weights: &CostWeights) -> usize {
+            let (floor, direction) = self.call;
+            (0..self.elevators.len())
+                .min_by_key(|&id| {
+                    let (task, _) = &self.elevators[id];
+                    task.cost_function(floor, direction, weights)
+                })
+                .unwrap()
+        }
+
+        /// Elevator picked by the shortest time to idle with the call
+        fn time_to_idle_choice(&self, timing: Timing) -> usize {
+            (0..self.elevators.len())
+                .min_by_key(|&id| self.time_to_idle(id, true, timing))
+                .unwrap()
+        }
+
+        /// Time until every elevator is done, when the call is given to `chosen`
+        fn makespan(&self, chosen: usize, timing: Timing) -> Duration {
+            (0..self.elevators.len())
+                .map(|id| self.time_to_idle(id, id == chosen, timing))
+                .max()
+                .unwrap()
+        }
+    }
+
+    #[test]
+    fn time_to_idle_assigns_better_than_cost_function() {
+        let timing = Timing::default();
+        let weights = CostWeights::default();
+        let mut rng = Rng(2024);
+
+        // The cost function picks the closest elevator, which first has to go to the
+        // top floor, while the other one passes the call on its way down
+        N_FLOORS.get_or_init(|| 4);
+        let mut near = TaskInfo::new(0, mpsc::channel(1).0);
+        near.floor = Floor::from(1);
+        near.state = State::Moving(Direction::Up);
+        let near_requests = vec![(Button::Hall(Direction::Up), Floor::from(3))];
+        let mut far = TaskInfo::new(1, mpsc::channel(1).0);
+        far.floor = Floor::from(2);
+        far.state = State::Moving(Direction::Down);
+        let far_requests = vec![(Button::Cab, Floor::from(1))];
+        for (task, requests) in [(&mut near, &near_requests), (&mut far, &far_requests)] {
+            for &(button, floor) in requests {
+                task.requests.add_request(button, floor);
+            }
+        }
+        let situation = Situation {
+            elevators: vec![(near, near_requests), (far, far_requests)],
+            call: (Floor::from(0), Direction::Up),
         };
-        state_value + (floor_difference) + 2 * (n_requests) + (!in_direction as usize)
+        let heuristic = situation.cost_function_choice(&weights);
+        let time_to_idle = situation.time_to_idle_choice(timing);
+        assert_eq!((heuristic, time_to_idle), (0, 1));
+        assert!(situation.makespan(time_to_idle, timing) < situation.makespan(heuristic, timing));
+
+        let (mut heuristic_total, mut time_to_idle_total) = (Duration::ZERO, Duration::ZERO);
+        let mut differed = 0;
+        for _ in 0..1000 {
+            let situation = Situation::random(&mut rng, 3);
+            let heuristic = situation.makespan(situation.cost_function_choice(&weights), timing);
+            let time_to_idle = situation.makespan(situation.time_to_idle_choice(timing), timing);
+            assert!(time_to_idle <= heuristic);
+            differed += usize::from(time_to_idle < heuristic);
+            heuristic_total += heuristic;
+            time_to_idle_total += time_to_idle;
+        }
+
+        // Better in about one in seven situations, and never worse
+        assert!(differed > 100, "better in only {differed} situations");
+        assert!(time_to_idle_total < heuristic_total);
+    }
+
+    #[test]
+    fn prefers_the_elevator_that_is_done_sooner() {
+        N_FLOORS.get_or_init(|| 4);
+        let timing = Timing::default();
+        let weights = CostWeights::default();
+        let (floor, direction) = (Floor::from(0), Direction::Up);
+
+        // Closest to the call, but on its way to the top floor first
+        let mut near = TaskInfo::new(0, mpsc::channel(1).0);
+        near.floor = Floor::from(1);
+        near.state = State::Moving(Direction::Up);
+        near.requests
+            .add_request(Button::Hall(Direction::Up), Floor::from(3));
+        let mut near_requests = near.requests.clone();
+
+        // Further away, but heading down to drop someone off on the way
+        let mut far = TaskInfo::new(1, mpsc::channel(1).0);
+        far.floor = Floor::from(2);
+        far.state = State::Moving(Direction::Down);
+        far.requests.add_request(Button::Cab, Floor::from(1));
+        let mut far_requests = far.requests.clone();
+
+        assert!(
+            near.cost_function(floor, direction, &weights)
+                < far.cost_function(floor, direction, &weights)
+        );
+
+        near_requests.add_request(Button::Hall(direction), floor);
+        far_requests.add_request(Button::Hall(direction), floor);
+        let near = near.time_to_idle(near_requests, timing);
+        let far = far.time_to_idle(far_requests, timing);
+        assert_eq!(
+            (near, far),
+            (Duration::from_secs(15), Duration::from_secs(9))
+        );
+    }
+
+    #[test]
+    fn existing_stop_on_the_way_costs_nothing() {
+        N_FLOORS.get_or_init(|| 4);
+        let timing = Timing::default();
+        let weights = CostWeights::default();
+
+        // Going up from floor 0 to drop someone off at floor 2
+        let mut busy = TaskInfo::new(0, mpsc::channel(1).0);
+        busy.state = State::Moving(Direction::Up);
+        busy.requests.add_request(Button::Cab, Floor::from(2));
+        let idle = TaskInfo::new(1, mpsc::channel(1).0);
+
+        let up = Direction::Up;
+        assert!(busy.stops_on_the_way(Floor::from(2), up));
+        assert_eq!(busy.cost_function(Floor::from(2), up, &weights), 0);
+        assert!(idle.cost_function(Floor::from(2), up, &weights) > 0);
+
+        // Not when going the other way, or past the car
+        assert!(!busy.stops_on_the_way(Floor::from(2), Direction::Down));
+        busy.floor = Floor::from(2);
+        assert!(!busy.stops_on_the_way(Floor::from(2), up));
+
+        // The stop is made anyway, so the call adds no time either
+        busy.floor = Floor::from(0);
+        let mut requests = busy.cab_requests();
+        let without_call = busy.time_to_idle(requests.clone(), timing);
+        requests.add_request(Button::Hall(up), Floor::from(2));
+        assert_eq!(busy.time_to_idle(requests, timing), without_call);
     }
 }
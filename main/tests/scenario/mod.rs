@@ -0,0 +1,457 @@
+//! Scripted end-to-end scenarios, run against in-memory simulators on a virtual clock
+//!
+//! Scenarios must run in a test with a paused clock, `#[tokio::test(start_paused = true)]`,
+//! so that minutes of elevator time pass in a fraction of a second.
+
+// Not every test file uses every helper
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::io::{Error, ErrorKind, Result};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, Instant};
+
+use interface::types::Button;
+use simulator::{Settings, Simulator, SimulatorDriver, Snapshot};
+
+use elevators::{Config, Hardware, Strategy};
+
+/// Time between each sample of the simulators
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(50);
+/// Time a button is held down when pressed
+const PRESS_DURATION: Duration = Duration::from_millis(200);
+
+static SCENARIO_ID: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug, Clone, Copy)]
+enum Action {
+    Press(Button, usize),
+    Release(Button, usize),
+    Obstruct(bool),
+    StopButton(bool),
+    MotorPower(bool),
+    /// Cuts (false) or restores (true) the connection to the hardware
+    Connect(bool),
+    /// Crashes (false) or starts again (true) the software of a single elevator
+    Alive(bool),
+    /// Crashes the process running the elevators, applies to every elevator
+    KillProcess,
+    /// Starts the process again after a crash, applies to every elevator
+    RestartProcess,
+}
+
+/// A sequence of events to apply to the elevators, built with the methods below
+///
+/// ```ignore
+/// Scenario::new(2, 4)
+///     .press(0.0, 0, Button::Hall(Direction::Up), 2)
+///     .obstruct(3.0, 0, true)
+///     .disconnect(5.0, 1)
+///     .kill(8.0, 0)
+///     .restart(10.0, 0)
+///     .run(60.0)
+///     .await
+///     .assert_served_within(20.0);
+/// ```
+pub struct Scenario {
+    n_elevators: usize,
+    n_floors: usize,
+    settings: Settings,
+    door_open_secs: f64,
+    assigner: Strategy,
+    actions: Vec<(Duration, usize, Action)>,
+}
+
+/// Samples of every elevator taken while running a scenario
+pub struct Report {
+    samples: Vec<Sample>,
+}
+
+struct Sample {
+    time: Duration,
+    /// Snapshot of each elevator, None while it is disconnected or killed
+    elevators: Vec<Option<Snapshot>>,
+}
+
+/// Hands out in-memory drivers, refusing to connect to disconnected simulators
+/// and to elevators that are killed
+struct Simulated {
+    drivers: Vec<SimulatorDriver>,
+    alive: Arc<Vec<watch::Sender<bool>>>,
+}
+
+impl Scenario {
+    pub fn new(n_elevators: usize, n_floors: usize) -> Scenario {
+        Scenario {
+            n_elevators,
+            n_floors,
+            settings: Settings {
+                n_floors,
+                ..Settings::default()
+            },
+            door_open_secs: 3.0,
+            assigner: Strategy::default(),
+            actions: Vec::new(),
+        }
+    }
+
+    /// Assigns hall requests with the given strategy instead of the default one
+    pub fn assigner(mut self, assigner: Strategy) -> Scenario {
+        self.assigner = assigner;
+        self
+    }
+
+    /// Presses a button at an elevator, and lets go of it shortly after
+    pub fn press(mut self, at: f64, elevator: usize, button: Button, floor: usize) -> Scenario {
+        let at = secs(at);
+        self.actions
+            .push((at, elevator, Action::Press(button, floor)));
+        let release = Action::Release(button, floor);
+        self.actions.push((at + PRESS_DURATION, elevator, release));
+        self
+    }
+
+    pub fn obstruct(mut self, at: f64, elevator: usize, on: bool) -> Scenario {
+        self.actions
+            .push((secs(at), elevator, Action::Obstruct(on)));
+        self
+    }
+
+    /// Presses the stop button of an elevator, and lets go of it shortly after
+    pub fn press_stop(mut self, at: f64, elevator: usize) -> Scenario {
+        let at = secs(at);
+        self.actions.push((at, elevator, Action::StopButton(true)));
+        let release = Action::StopButton(false);
+        self.actions.push((at + PRESS_DURATION, elevator, release));
+        self
+    }
+
+    /// Cuts (false) or restores (true) the power of the motor of an elevator
+    pub fn motor_power(mut self, at: f64, elevator: usize, on: bool) -> Scenario {
+        self.actions
+            .push((secs(at), elevator, Action::MotorPower(on)));
+        self
+    }
+
+    /// Cuts the connection between an elevator and its hardware, the car keeps going
+    pub fn disconnect(mut self, at: f64, elevator: usize) -> Scenario {
+        self.actions
+            .push((secs(at), elevator, Action::Connect(false)));
+        self
+    }
+
+    /// Restores the connection of an elevator that was disconnected
+    pub fn reconnect(mut self, at: f64, elevator: usize) -> Scenario {
+        self.actions
+            .push((secs(at), elevator, Action::Connect(true)));
+        self
+    }
+
+    /// Crashes the software of an elevator, aborting its state machine without any
+    /// cleanup. The car keeps doing whatever it was doing, and its button lights stay
+    /// as they were, while the other elevators keep running.
+    pub fn kill(mut self, at: f64, elevator: usize) -> Scenario {
+        self.actions
+            .push((secs(at), elevator, Action::Alive(false)));
+        self
+    }
+
+    /// Starts the software of an elevator again after it was killed
+    pub fn restart(mut self, at: f64, elevator: usize) -> Scenario {
+        self.actions.push((secs(at), elevator, Action::Alive(true)));
+        self
+    }
+
+    /// Crashes the process running the elevators, aborting every task without any
+    /// cleanup. The cars keep doing whatever they were doing, and every button light
+    /// stays as it was.
+    ///
+    /// All elevators of a scenario run in the same process, so they all go down.
+    pub fn kill_process(mut self, at: f64) -> Scenario {
+        self.actions.push((secs(at), 0, Action::KillProcess));
+        self
+    }
+
+    /// Starts the process again after it was killed, with the same state directory,
+    /// so that the cab calls stored before the crash are restored
+    pub fn restart_process(mut self, at: f64) -> Scenario {
+        self.actions.push((secs(at), 0, Action::RestartProcess));
+        self
+    }
+
+    /// Runs the scenario for the given number of seconds
+    pub async fn run(mut self, duration: f64) -> Report {
+        let simulators: Vec<Simulator> = (0..self.n_elevators)
+            .map(|_| Simulator::new(self.settings))
+            .collect();
+
+        let id = SCENARIO_ID.fetch_add(1, Ordering::Relaxed);
+        let state_dir =
+            std::env::temp_dir().join(format!("elevators-scenario-{}-{id}", std::process::id()));
+        let args = format!(
+            "elevators --elevators {} --floors {} --door-open-secs {} --assigner {} --state-dir {}",
+            self.n_elevators,
+            self.n_floors,
+            self.door_open_secs,
+            self.assigner,
+            state_dir.display()
+        );
+        let alive = simulators.iter().map(|_| watch::Sender::new(true));
+        let alive = Arc::new(alive.collect::<Vec<_>>());
+        let mut process = Some(launch(&args, &simulators, &alive));
+
+        self.actions.sort_by_key(|&(at, ..)| at);
+        let mut actions = self.actions.into_iter().peekable();
+        let mut samples = Vec::new();
+        let start = Instant::now();
+        while start.elapsed() < secs(duration) {
+            let time = start.elapsed();
+            while let Some((_, elevator, action)) = actions.next_if(|&(at, ..)| at <= time) {
+                match action {
+                    Action::Alive(on) => {
+                        alive[elevator].send_replace(on);
+                    }
+                    Action::KillProcess => {
+                        if let Some(process) = process.take() {
+                            process.abort();
+                        }
+                    }
+                    Action::RestartProcess => {
+                        process.get_or_insert_with(|| launch(&args, &simulators, &alive));
+                    }
+                    action => apply(&simulators[elevator], action),
+                }
+            }
+
+            let elevators = simulators
+                .iter()
+                .zip(alive.iter())
+                .map(|(simulator, alive)| {
+                    let connected = simulator.is_connected() && *alive.borrow();
+                    connected.then(|| simulator.snapshot())
+                })
+                .collect();
+            samples.push(Sample { time, elevators });
+
+            sleep(SAMPLE_INTERVAL).await;
+        }
+
+        if let Some(process) = process {
+            process.abort();
+        }
+        let _ = std::fs::remove_dir_all(state_dir);
+        Report { samples }
+    }
+}
+
+impl Report {
+    /// Every button light that is turned on is turned off again within the given
+    /// number of seconds, meaning the call was served
+    ///
+    /// Lights of disconnected or killed elevators are not checked, since they can not
+    /// be updated.
+    pub fn assert_served_within(&self, limit: f64) -> &Report {
+        let limit = secs(limit);
+        let mut lit_since: HashMap<(usize, Button, usize), Duration> = HashMap::new();
+
+        for sample in self.samples.iter() {
+            for (elevator, snapshot) in sample.elevators.iter().enumerate() {
+                let Some(snapshot) = snapshot else {
+                    lit_since.retain(|&(id, ..), _| id != elevator);
+                    continue;
+                };
+                lit_since.retain(|&(id, button, floor), _| {
+                    id != elevator || snapshot.order_lights.contains(&(button, floor))
+                });
+                for &(button, floor) in snapshot.order_lights.iter() {
+                    lit_since
+                        .entry((elevator, button, floor))
+                        .or_insert(sample.time);
+                }
+            }
+
+            for (&(elevator, button, floor), &since) in lit_since.iter() {
+                assert!(
+                    sample.time - since <= limit,
+                    "{button:?} at floor {floor} was lit at elevator {elevator} from {since:?} \
+                     until at least {:?}",
+                    sample.time
+                );
+            }
+        }
+        self
+    }
+
+    /// No button lights are left on at the end of the scenario
+    pub fn assert_all_served(&self) -> &Report {
+        let last = self.samples.last().expect("scenario has no samples");
+        for (elevator, snapshot) in last.elevators.iter().enumerate() {
+            if let Some(snapshot) = snapshot {
+                assert!(
+                    snapshot.order_lights.is_empty(),
+                    "elevator {elevator} still has lights on: {:?}",
+                    snapshot.order_lights
+                );
+            }
+        }
+        self
+    }
+
+    /// The door is never open while the motor is running
+    pub fn assert_door_closed_while_moving(&self) -> &Report {
+        for sample in self.samples.iter() {
+            for (elevator, snapshot) in sample.elevators.iter().enumerate() {
+                if let Some(snapshot) = snapshot {
+                    assert!(
+                        !(snapshot.door_open_light && snapshot.motor != 0),
+                        "door of elevator {elevator} was open while moving at {:?}: {snapshot:?}",
+                        sample.time
+                    );
+                }
+            }
+        }
+        self
+    }
+
+    /// The door of the elevator is open during the whole interval, in seconds
+    pub fn assert_door_open(&self, elevator: usize, from: f64, to: f64) -> &Report {
+        let (from, to) = (secs(from), secs(to));
+        let samples = self.samples.iter().filter(|s| s.time >= from && s.time <= to);
+        for sample in samples {
+            let open = sample.elevators[elevator]
+                .as_ref()
+                .is_some_and(|s| s.door_open_light);
+            assert!(open, "door of elevator {elevator} was closed at {:?}", sample.time);
+        }
+        self
+    }
+
+    /// The elevator is halted between floors with the door closed and the stop
+    /// light on during the whole interval, in seconds
+    pub fn assert_stopped_between_floors(&self, elevator: usize, from: f64, to: f64) -> &Report {
+        let (from, to) = (secs(from), secs(to));
+        let samples = self.samples.iter().filter(|s| s.time >= from && s.time <= to);
+        for sample in samples {
+            let stopped = sample.elevators[elevator].as_ref().is_some_and(|s| {
+                s.motor == 0 && s.floor.is_none() && !s.door_open_light && s.stop_button_light
+            });
+            assert!(
+                stopped,
+                "elevator {elevator} was not stopped between floors at {:?}: {:?}",
+                sample.time, sample.elevators[elevator]
+            );
+        }
+        self
+    }
+
+    /// The elevator stopped with the door open at the floor at some point
+    pub fn assert_visited(&self, elevator: usize, floor: usize) -> &Report {
+        let visited = self.samples.iter().any(|sample| {
+            sample.elevators[elevator]
+                .as_ref()
+                .is_some_and(|s| s.floor == Some(floor) && s.door_open_light)
+        });
+        assert!(
+            visited,
+            "elevator {elevator} never opened its door at floor {floor}"
+        );
+        self
+    }
+
+    /// The elevator never stopped with the door open at the floor
+    pub fn assert_not_visited(&self, elevator: usize, floor: usize) -> &Report {
+        self.assert_not_visited_before(elevator, floor, f64::MAX)
+    }
+
+    /// The elevator did not stop with the door open at the floor before the given time,
+    /// in seconds
+    pub fn assert_not_visited_before(&self, elevator: usize, floor: usize, until: f64) -> &Report {
+        let until = Duration::try_from_secs_f64(until).unwrap_or(Duration::MAX);
+        let mut samples = self.samples.iter().filter(|s| s.time < until);
+        let visited = samples.find(|sample| {
+            sample.elevators[elevator]
+                .as_ref()
+                .is_some_and(|s| s.floor == Some(floor) && s.door_open_light)
+        });
+        if let Some(sample) = visited {
+            panic!(
+                "elevator {elevator} opened its door at floor {floor} at {:?}",
+                sample.time
+            );
+        }
+        self
+    }
+}
+
+impl Hardware for Simulated {
+    type Driver = SimulatorDriver;
+
+    async fn connect(&self, task_id: usize) -> Result<SimulatorDriver> {
+        let driver = self.drivers[task_id].clone();
+        if !interface::HardwareDriver::is_connected(&driver) {
+            return Err(Error::new(
+                ErrorKind::ConnectionRefused,
+                "simulator is disconnected",
+            ));
+        }
+        if !*self.alive[task_id].borrow() {
+            return Err(Error::new(
+                ErrorKind::ConnectionRefused,
+                "elevator is killed",
+            ));
+        }
+        Ok(driver)
+    }
+
+    fn describe(&self, task_id: usize) -> String {
+        format!("in-memory simulator {task_id}")
+    }
+
+    fn crashed(&self, task_id: usize) -> impl Future<Output = ()> + Send {
+        let mut alive = self.alive[task_id].subscribe();
+        async move {
+            let _ = alive.wait_for(|&alive| !alive).await;
+        }
+    }
+}
+
+/// Starts the process running the elevators, with the given command-line arguments
+///
+/// Aborting the returned handle drops the dispatcher, after which the supervisors and
+/// state machines stop as soon as they notice, like the rest of a crashed process.
+fn launch(
+    args: &str,
+    simulators: &[Simulator],
+    alive: &Arc<Vec<watch::Sender<bool>>>,
+) -> JoinHandle<bool> {
+    let config = Config::build_from(args.split_whitespace().map(String::from), |_| None)
+        .expect("invalid scenario config");
+    let hardware = Simulated {
+        drivers: simulators.iter().map(Simulator::driver).collect(),
+        alive: alive.clone(),
+    };
+    tokio::spawn(async move { elevators::run_with(config, hardware).await.is_ok() })
+}
+
+fn apply(simulator: &Simulator, action: Action) {
+    match action {
+        Action::Press(button, floor) => simulator.press(button, floor),
+        Action::Release(button, floor) => simulator.release(button, floor),
+        Action::Obstruct(on) => simulator.set_obstruction(on),
+        Action::StopButton(pressed) => simulator.set_stop_button(pressed),
+        Action::MotorPower(on) => simulator.set_motor_power(on),
+        Action::Connect(connected) => simulator.set_connected(connected),
+        Action::Alive(_) | Action::KillProcess | Action::RestartProcess => {
+            unreachable!("{action:?} applies to the software, not the simulator")
+        }
+    }
+}
+
+fn secs(secs: f64) -> Duration {
+    Duration::from_secs_f64(secs)
+}
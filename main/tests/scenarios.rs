@@ -0,0 +1,183 @@
+//! End-to-end checks of the rules in requirements.md, see scenario/mod.rs
+
+use interface::types::{Button, Direction};
+
+use elevators::Strategy;
+
+mod scenario;
+
+use scenario::Scenario;
+
+const UP: Button = Button::Hall(Direction::Up);
+const DOWN: Button = Button::Hall(Direction::Down);
+
+#[tokio::test(start_paused = true)]
+async fn hall_and_cab_calls_are_served() {
+    Scenario::new(2, 4)
+        .press(0.0, 0, UP, 2)
+        .press(0.5, 1, DOWN, 3)
+        .press(1.0, 0, Button::Cab, 3)
+        .press(4.0, 1, UP, 0)
+        .press(6.0, 1, Button::Cab, 1)
+        .run(60.0)
+        .await
+        .assert_served_within(20.0)
+        .assert_all_served()
+        .assert_door_closed_while_moving()
+        .assert_visited(0, 3)
+        .assert_visited(1, 1);
+}
+
+#[tokio::test(start_paused = true)]
+async fn every_assigner_serves_all_calls() {
+    let strategies = [
+        Strategy::TimeToIdle,
+        Strategy::Nearest,
+        Strategy::RoundRobin,
+        Strategy::LeastLoaded,
+    ];
+    for strategy in strategies {
+        Scenario::new(3, 4)
+            .assigner(strategy)
+            .press(0.0, 0, UP, 1)
+            .press(0.5, 1, DOWN, 3)
+            .press(1.0, 2, UP, 2)
+            .press(1.5, 0, DOWN, 2)
+            .press(2.0, 1, Button::Cab, 0)
+            .press(5.0, 2, UP, 0)
+            .run(60.0)
+            .await
+            .assert_served_within(30.0)
+            .assert_all_served()
+            .assert_door_closed_while_moving();
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn cab_calls_are_only_served_by_their_elevator() {
+    Scenario::new(2, 4)
+        .press(0.0, 1, Button::Cab, 3)
+        .run(30.0)
+        .await
+        .assert_all_served()
+        .assert_visited(1, 3)
+        .assert_not_visited(0, 3);
+}
+
+#[tokio::test(start_paused = true)]
+async fn hall_calls_move_away_from_a_disconnected_elevator() {
+    Scenario::new(2, 4)
+        .press(0.0, 0, UP, 2)
+        .disconnect(0.5, 0)
+        .reconnect(30.0, 0)
+        .run(60.0)
+        .await
+        .assert_served_within(20.0)
+        .assert_all_served()
+        .assert_door_closed_while_moving()
+        .assert_visited(1, 2);
+}
+
+#[tokio::test(start_paused = true)]
+async fn a_stopped_elevator_does_not_hold_up_the_others() {
+    // Elevator 1 is never started, while far more hall lights than fit in its mailbox
+    // are turned on and off
+    let mut scenario = Scenario::new(2, 4).disconnect(0.0, 1);
+    for i in 0..12 {
+        let at = 1.0 + 30.0 * i as f64;
+        for (floor, button) in [(1, UP), (2, UP), (3, DOWN), (2, DOWN), (1, DOWN), (0, UP)] {
+            scenario = scenario.press(at, 0, button, floor);
+        }
+    }
+    scenario
+        .run(380.0)
+        .await
+        .assert_served_within(30.0)
+        .assert_all_served();
+}
+
+#[tokio::test(start_paused = true)]
+async fn cab_calls_are_served_after_a_crash() {
+    // Killed while elevator 0 is on its way, so the calls are only kept on disk
+    Scenario::new(2, 4)
+        .press(0.0, 0, Button::Cab, 3)
+        .press(0.0, 1, Button::Cab, 2)
+        .kill_process(0.5)
+        .restart_process(10.0)
+        .run(60.0)
+        .await
+        .assert_not_visited_before(0, 3, 10.0)
+        .assert_visited(0, 3)
+        .assert_visited(1, 2)
+        .assert_all_served()
+        .assert_door_closed_while_moving();
+}
+
+#[tokio::test(start_paused = true)]
+async fn hall_calls_move_away_from_a_killed_elevator() {
+    // Elevator 0 is on its way up, so elevator 1 takes the call at floor 1 and is
+    // killed before it gets there
+    Scenario::new(2, 4)
+        .press(0.0, 0, Button::Cab, 3)
+        .press(0.5, 0, DOWN, 1)
+        .kill(1.5, 1)
+        .restart(30.0, 1)
+        .run(60.0)
+        .await
+        .assert_not_visited_before(1, 1, 30.0)
+        .assert_visited(0, 3)
+        .assert_visited(0, 1)
+        .assert_served_within(20.0)
+        .assert_all_served()
+        .assert_door_closed_while_moving();
+}
+
+#[tokio::test(start_paused = true)]
+async fn obstructed_door_stays_open_and_hall_calls_move_on() {
+    // Elevator 1 is away, so that the hall call goes to the obstructed elevator 0
+    Scenario::new(2, 4)
+        .disconnect(0.0, 1)
+        .press(0.5, 0, Button::Cab, 0)
+        .obstruct(1.0, 0, true)
+        .press(2.0, 0, UP, 2)
+        .reconnect(3.0, 1)
+        .obstruct(40.0, 0, false)
+        .run(60.0)
+        .await
+        .assert_door_open(0, 1.0, 40.0)
+        .assert_visited(1, 2)
+        .assert_served_within(30.0)
+        .assert_all_served()
+        .assert_door_closed_while_moving();
+}
+
+#[tokio::test(start_paused = true)]
+async fn emergency_stop_between_floors_and_resume() {
+    Scenario::new(2, 4)
+        .press(0.0, 0, Button::Cab, 3)
+        .press(0.0, 0, UP, 2)
+        .press_stop(1.0, 0)
+        .press_stop(20.0, 0)
+        .run(60.0)
+        .await
+        .assert_stopped_between_floors(0, 1.5, 20.0)
+        .assert_visited(1, 2)
+        .assert_visited(0, 3)
+        .assert_all_served()
+        .assert_door_closed_while_moving();
+}
+
+#[tokio::test(start_paused = true)]
+async fn hall_calls_move_away_from_a_broken_motor() {
+    Scenario::new(2, 4)
+        .press(0.0, 0, Button::Cab, 3)
+        .press(0.0, 0, UP, 2)
+        .motor_power(1.0, 0, false)
+        .motor_power(20.0, 0, true)
+        .run(60.0)
+        .await
+        .assert_visited(1, 2)
+        .assert_visited(0, 3)
+        .assert_not_visited(0, 2)
+        .assert_all_served();
+}
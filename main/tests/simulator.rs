@@ -0,0 +1,79 @@
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::time::{sleep, timeout};
+
+use interface::types::{Button, Direction};
+use simulator::{Settings, Simulator, Snapshot};
+
+use elevators::Config;
+
+const N_ELEVATORS: usize = 2;
+
+async fn start_simulators() -> Vec<Simulator> {
+    let settings = Settings {
+        travel_time: Duration::from_millis(1000),
+        ..Settings::default()
+    };
+    let mut simulators = Vec::new();
+    for _ in 0..N_ELEVATORS {
+        let addr = SocketAddr::from(([127, 0, 0, 1], 0));
+        simulators.push(Simulator::start(addr, settings).await.unwrap());
+    }
+    simulators
+}
+
+/// Waits until the snapshot of the simulator satisfies the condition
+async fn wait_until(simulator: &Simulator, condition: impl Fn(&Snapshot) -> bool) {
+    let wait = async {
+        while !condition(&simulator.snapshot()) {
+            sleep(Duration::from_millis(10)).await;
+        }
+    };
+    if timeout(Duration::from_secs(10), wait).await.is_err() {
+        panic!("timed out, last snapshot: {:?}", simulator.snapshot());
+    }
+}
+
+#[tokio::test]
+async fn serves_cab_and_hall_calls() {
+    let simulators = start_simulators().await;
+    let ports: Vec<String> = simulators
+        .iter()
+        .map(|simulator| simulator.addr().unwrap().port().to_string())
+        .collect();
+    let state_dir = std::env::temp_dir().join(format!("elevators-it-{}", std::process::id()));
+    let args = format!(
+        "elevators --ports {} --door-open-secs 0.5 --state-dir {}",
+        ports.join(","),
+        state_dir.display()
+    );
+    let config = Config::build_from(args.split_whitespace().map(String::from), |_| None).unwrap();
+    tokio::spawn(async move { elevators::run(config).await.is_ok() });
+
+    // Cab call
+    simulators[0].press(Button::Cab, 3);
+    wait_until(&simulators[0], |s| {
+        s.order_lights.contains(&(Button::Cab, 3))
+    })
+    .await;
+    simulators[0].release(Button::Cab, 3);
+    wait_until(&simulators[0], |s| s.floor == Some(3) && s.door_open_light).await;
+    wait_until(&simulators[0], |s| s.order_lights.is_empty()).await;
+
+    // Hall call, with the light shown at every elevator
+    let button = Button::Hall(Direction::Down);
+    simulators[1].press(button, 2);
+    for simulator in simulators.iter() {
+        wait_until(simulator, |s| s.order_lights.contains(&(button, 2))).await;
+    }
+    simulators[1].release(button, 2);
+    for simulator in simulators.iter() {
+        wait_until(simulator, |s| !s.order_lights.contains(&(button, 2))).await;
+    }
+    let served = simulators
+        .iter()
+        .any(|simulator| simulator.snapshot().floor == Some(2));
+    assert!(served);
+
+    let _ = std::fs::remove_dir_all(state_dir);
+}
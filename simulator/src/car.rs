@@ -0,0 +1,205 @@
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::time::Instant;
+
+use interface::types::Button;
+
+/// Distance from a floor, in floors, where the floor sensor still detects the car
+const SENSOR_RANGE: f64 = 0.1;
+
+/// Physical state of a simulated elevator car
+#[derive(Debug, Clone)]
+pub struct Car {
+    n_floors: usize,
+    travel_time: Duration,
+    /// Position of the car, 0.0 being the bottom floor
+    position: f64,
+    /// -1, 0 or 1
+    motor: i8,
+    /// The car does not move while the motor has no power, whatever the direction
+    powered: bool,
+    updated: Instant,
+
+    // Inputs
+    pressed: HashMap<(Button, usize), bool>,
+    stop_button: bool,
+    obstruction: bool,
+
+    // Outputs
+    order_lights: HashMap<(Button, usize), bool>,
+    floor_indicator: usize,
+    door_open_light: bool,
+    stop_button_light: bool,
+}
+
+impl Car {
+    pub fn new(n_floors: usize, travel_time: Duration, start_floor: usize) -> Car {
+        Car {
+            n_floors,
+            travel_time,
+            position: start_floor as f64,
+            motor: 0,
+            powered: true,
+            updated: Instant::now(),
+            pressed: HashMap::new(),
+            stop_button: false,
+            obstruction: false,
+            order_lights: HashMap::new(),
+            floor_indicator: start_floor,
+            door_open_light: false,
+            stop_button_light: false,
+        }
+    }
+
+    /// Moves the car according to the time passed since the last update
+    fn update(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+        self.updated = now;
+
+        if !self.powered {
+            return;
+        }
+
+        let distance = elapsed / self.travel_time.as_secs_f64();
+        let top = (self.n_floors - 1) as f64;
+        self.position = (self.position + f64::from(self.motor) * distance).clamp(0.0, top);
+    }
+
+    pub fn position(&mut self) -> f64 {
+        self.update();
+        self.position
+    }
+
+    /// The floor the car is at, or None if it is between floors
+    pub fn floor(&mut self) -> Option<usize> {
+        self.update();
+        let nearest = self.position.round();
+        ((self.position - nearest).abs() <= SENSOR_RANGE).then_some(nearest as usize)
+    }
+
+    pub fn motor(&self) -> i8 {
+        self.motor
+    }
+
+    pub fn set_motor(&mut self, direction: i8) {
+        self.update();
+        self.motor = direction.signum();
+    }
+
+    pub fn set_motor_power(&mut self, powered: bool) {
+        self.update();
+        self.powered = powered;
+    }
+
+    pub fn is_pressed(&self, button: Button, floor: usize) -> bool {
+        self.pressed.get(&(button, floor)) == Some(&true)
+    }
+
+    pub fn set_pressed(&mut self, button: Button, floor: usize, pressed: bool) {
+        self.pressed.insert((button, floor), pressed);
+    }
+
+    pub fn stop_button(&self) -> bool {
+        self.stop_button
+    }
+
+    pub fn set_stop_button(&mut self, pressed: bool) {
+        self.stop_button = pressed;
+    }
+
+    pub fn obstruction(&self) -> bool {
+        self.obstruction
+    }
+
+    pub fn set_obstruction(&mut self, active: bool) {
+        self.obstruction = active;
+    }
+
+    pub fn is_lit(&self, button: Button, floor: usize) -> bool {
+        self.order_lights.get(&(button, floor)) == Some(&true)
+    }
+
+    pub fn set_order_light(&mut self, button: Button, floor: usize, on: bool) {
+        self.order_lights.insert((button, floor), on);
+    }
+
+    pub fn floor_indicator(&self) -> usize {
+        self.floor_indicator
+    }
+
+    pub fn set_floor_indicator(&mut self, floor: usize) {
+        self.floor_indicator = floor;
+    }
+
+    pub fn door_open_light(&self) -> bool {
+        self.door_open_light
+    }
+
+    pub fn set_door_open_light(&mut self, on: bool) {
+        self.door_open_light = on;
+    }
+
+    pub fn stop_button_light(&self) -> bool {
+        self.stop_button_light
+    }
+
+    pub fn set_stop_button_light(&mut self, on: bool) {
+        self.stop_button_light = on;
+    }
+
+    pub fn n_floors(&self) -> usize {
+        self.n_floors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::time::advance;
+
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn moves_between_floors() {
+        let mut car = Car::new(4, Duration::from_secs(2), 1);
+        assert_eq!(car.floor(), Some(1));
+
+        car.set_motor(1);
+        advance(Duration::from_secs(1)).await;
+        assert_eq!(car.floor(), None);
+        advance(Duration::from_secs(1)).await;
+        assert_eq!(car.floor(), Some(2));
+
+        car.set_motor(0);
+        advance(Duration::from_secs(10)).await;
+        assert_eq!(car.floor(), Some(2));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn stands_still_without_power() {
+        let mut car = Car::new(4, Duration::from_secs(2), 0);
+
+        car.set_motor(1);
+        advance(Duration::from_secs(1)).await;
+        car.set_motor_power(false);
+        advance(Duration::from_secs(10)).await;
+        assert_eq!(car.position(), 0.5);
+
+        car.set_motor_power(true);
+        advance(Duration::from_secs(1)).await;
+        assert_eq!(car.floor(), Some(1));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn stops_at_the_ends() {
+        let mut car = Car::new(4, Duration::from_secs(2), 1);
+
+        car.set_motor(-1);
+        advance(Duration::from_secs(10)).await;
+        assert_eq!(car.position(), 0.0);
+
+        car.set_motor(1);
+        advance(Duration::from_secs(60)).await;
+        assert_eq!(car.floor(), Some(3));
+    }
+}
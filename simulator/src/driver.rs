@@ -0,0 +1,89 @@
+use std::io::{Error, ErrorKind, Result};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use interface::types::{Button, Direction, Floor};
+use interface::HardwareDriver;
+
+use crate::car::Car;
+
+/// Controls a simulated elevator in the same process, without going through TCP
+///
+/// Every call fails with ErrorKind::NotConnected while the simulator is disconnected.
+#[derive(Debug, Clone)]
+pub struct SimulatorDriver {
+    car: Arc<Mutex<Car>>,
+    connected: Arc<AtomicBool>,
+}
+
+impl SimulatorDriver {
+    pub(crate) fn new(car: Arc<Mutex<Car>>, connected: Arc<AtomicBool>) -> SimulatorDriver {
+        SimulatorDriver { car, connected }
+    }
+
+    fn car(&self) -> Result<MutexGuard<'_, Car>> {
+        if !self.is_connected() {
+            return Err(Error::new(
+                ErrorKind::NotConnected,
+                "simulator is disconnected",
+            ));
+        }
+        Ok(self.car.lock().unwrap())
+    }
+}
+
+impl HardwareDriver for SimulatorDriver {
+    fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::Relaxed)
+    }
+
+    async fn motor_direction(&mut self, direction: Direction) -> Result<()> {
+        let direction = match direction {
+            Direction::Up => 1,
+            Direction::Down => -1,
+        };
+        self.car()?.set_motor(direction);
+        Ok(())
+    }
+
+    async fn stop(&mut self) -> Result<()> {
+        self.car()?.set_motor(0);
+        Ok(())
+    }
+
+    async fn order_button_light(&mut self, button: Button, floor: Floor, on: bool) -> Result<()> {
+        self.car()?.set_order_light(button, floor.get(), on);
+        Ok(())
+    }
+
+    async fn floor_indicator(&mut self, floor: Floor) -> Result<()> {
+        self.car()?.set_floor_indicator(floor.get());
+        Ok(())
+    }
+
+    async fn door_open_light(&mut self, on: bool) -> Result<()> {
+        self.car()?.set_door_open_light(on);
+        Ok(())
+    }
+
+    async fn stop_button_light(&mut self, on: bool) -> Result<()> {
+        self.car()?.set_stop_button_light(on);
+        Ok(())
+    }
+
+    async fn order_button(&mut self, button: Button, floor: Floor) -> Result<bool> {
+        Ok(self.car()?.is_pressed(button, floor.get()))
+    }
+
+    async fn floor(&mut self) -> Result<Option<Floor>> {
+        Ok(self.car()?.floor().and_then(Floor::from_value))
+    }
+
+    async fn stop_button(&mut self) -> Result<bool> {
+        Ok(self.car()?.stop_button())
+    }
+
+    async fn obstruction_switch(&mut self) -> Result<bool> {
+        Ok(self.car()?.obstruction())
+    }
+}
@@ -0,0 +1,162 @@
+//! Elevator simulator speaking the same 4-byte TCP protocol as Simulator-v2
+//!
+//! Used to run the controller, and its integration tests, without the external simulator.
+//! A simulator can also run in memory, controlled in the same process through SimulatorDriver.
+
+use std::io::Result;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+
+use interface::types::Button;
+
+mod car;
+mod driver;
+mod server;
+
+use self::car::Car;
+
+pub use self::driver::SimulatorDriver;
+
+const DEFAULT_N_FLOORS: usize = 4;
+const DEFAULT_TRAVEL_TIME: Duration = Duration::from_secs(2);
+
+/// Settings for a simulated elevator
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Settings {
+    pub n_floors: usize,
+    /// Time it takes the car to move from one floor to the next
+    pub travel_time: Duration,
+    pub start_floor: usize,
+}
+
+/// A running simulated elevator
+///
+/// The buttons, the stop button and the obstruction switch are controlled through
+/// this handle, as are reads of the outputs set by the controller.
+pub struct Simulator {
+    car: Arc<Mutex<Car>>,
+    connected: Arc<AtomicBool>,
+    server: Option<(SocketAddr, JoinHandle<()>)>,
+}
+
+/// Outputs and position of a simulated elevator at some point in time
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot {
+    pub position: f64,
+    pub floor: Option<usize>,
+    pub motor: i8,
+    pub floor_indicator: usize,
+    pub door_open_light: bool,
+    pub stop_button_light: bool,
+    /// Order button lights as (button, floor) that are turned on
+    pub order_lights: Vec<(Button, usize)>,
+}
+
+impl Simulator {
+    /// Creates a simulator that is only reached in memory, through driver()
+    pub fn new(settings: Settings) -> Simulator {
+        let Settings {
+            n_floors,
+            travel_time,
+            start_floor,
+        } = settings;
+
+        Simulator {
+            car: Arc::new(Mutex::new(Car::new(n_floors, travel_time, start_floor))),
+            connected: Arc::new(AtomicBool::new(true)),
+            server: None,
+        }
+    }
+
+    /// Starts a simulator listening on the given address
+    pub async fn start(addr: SocketAddr, settings: Settings) -> Result<Simulator> {
+        let listener = TcpListener::bind(addr).await?;
+        let addr = listener.local_addr()?;
+
+        let mut simulator = Simulator::new(settings);
+        let handle = tokio::spawn(server::serve(listener, simulator.car.clone()));
+        simulator.server = Some((addr, handle));
+        Ok(simulator)
+    }
+
+    /// Address the simulator listens on, useful when started on port 0
+    pub fn addr(&self) -> Option<SocketAddr> {
+        self.server.as_ref().map(|(addr, _)| *addr)
+    }
+
+    /// Driver controlling the simulated elevator from the same process
+    pub fn driver(&self) -> SimulatorDriver {
+        SimulatorDriver::new(self.car.clone(), self.connected.clone())
+    }
+
+    /// Connects or disconnects the drivers given by driver(), to simulate losing
+    /// the connection to the elevator. The car keeps going in the meantime.
+    pub fn set_connected(&self, connected: bool) {
+        self.connected.store(connected, Ordering::Relaxed);
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::Relaxed)
+    }
+
+    pub fn press(&self, button: Button, floor: usize) {
+        self.car.lock().unwrap().set_pressed(button, floor, true);
+    }
+
+    pub fn release(&self, button: Button, floor: usize) {
+        self.car.lock().unwrap().set_pressed(button, floor, false);
+    }
+
+    pub fn set_stop_button(&self, pressed: bool) {
+        self.car.lock().unwrap().set_stop_button(pressed);
+    }
+
+    pub fn set_obstruction(&self, active: bool) {
+        self.car.lock().unwrap().set_obstruction(active);
+    }
+
+    /// Cuts (false) or restores (true) the power of the motor, the car stands
+    /// still without it but the elevator does not notice
+    pub fn set_motor_power(&self, powered: bool) {
+        self.car.lock().unwrap().set_motor_power(powered);
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        let mut car = self.car.lock().unwrap();
+        let order_lights = (0..car.n_floors())
+            .flat_map(|floor| Button::iterator().map(move |button| (button, floor)))
+            .filter(|&(button, floor)| car.is_lit(button, floor))
+            .collect();
+
+        Snapshot {
+            position: car.position(),
+            floor: car.floor(),
+            motor: car.motor(),
+            floor_indicator: car.floor_indicator(),
+            door_open_light: car.door_open_light(),
+            stop_button_light: car.stop_button_light(),
+            order_lights,
+        }
+    }
+
+    /// Stops the simulator, closing every connection
+    pub fn shutdown(self) {
+        if let Some((_, handle)) = self.server {
+            handle.abort();
+        }
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            n_floors: DEFAULT_N_FLOORS,
+            travel_time: DEFAULT_TRAVEL_TIME,
+            start_floor: 0,
+        }
+    }
+}
@@ -0,0 +1,133 @@
+use std::env;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::process;
+use std::str::FromStr;
+use std::time::Duration;
+use tokio::io::{self, AsyncBufReadExt, BufReader};
+
+use interface::types::{Button, Direction};
+use simulator::{Settings, Simulator};
+
+const DEFAULT_PORT: u16 = 15657;
+
+const USAGE: &str = "\
+Usage: simulator [OPTIONS]
+
+Options:
+    --port <PORT>          Port to listen on [default: 15657]
+    --floors <M>           Number of floors [default: 4]
+    --travel-ms <MS>       Time to travel between two floors [default: 2000]
+    --start-floor <FLOOR>  Floor the car starts at [default: 0]
+    -h, --help             Print this message
+
+Commands, read from standard input:
+    press <button> <floor>    Hold a button down, where button is one of: up, down, cab
+    release <button> <floor>  Let go of a button
+    obstruct <on|off>         Set the obstruction switch
+    stop <on|off>             Set the stop button
+    power <on|off>            Cut or restore the power of the motor
+    status                    Print the state of the elevator";
+
+#[tokio::main]
+async fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        println!("{USAGE}");
+        return;
+    }
+
+    let (port, settings) = parse_args(&args).unwrap_or_else(|err| {
+        eprintln!("Problem parsing arguments: {err}");
+        eprintln!("{USAGE}");
+        process::exit(1);
+    });
+
+    let addr = SocketAddr::from((Ipv4Addr::UNSPECIFIED, port));
+    let simulator = Simulator::start(addr, settings).await.unwrap_or_else(|e| {
+        eprintln!("Could not listen on {addr} => error: {e}");
+        process::exit(1);
+    });
+    println!("Simulator listening on {}", simulator.addr().unwrap());
+
+    let mut lines = BufReader::new(io::stdin()).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        if let Err(err) = run_command(&simulator, &line) {
+            eprintln!("{err}");
+        }
+    }
+
+    // Keep running without input, e.g. when started in the background
+    std::future::pending::<()>().await;
+}
+
+fn parse_args(args: &[String]) -> Result<(u16, Settings), String> {
+    let mut port = DEFAULT_PORT;
+    let mut settings = Settings::default();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let value = args
+            .next()
+            .ok_or(format!("missing value for argument '{arg}'"))?;
+        match arg.as_str() {
+            "--port" => port = parse(arg, value)?,
+            "--floors" => settings.n_floors = parse(arg, value)?,
+            "--travel-ms" => settings.travel_time = Duration::from_millis(parse(arg, value)?),
+            "--start-floor" => settings.start_floor = parse(arg, value)?,
+            _ => return Err(format!("unknown argument '{arg}'")),
+        }
+    }
+
+    if settings.n_floors < 2 {
+        return Err("'--floors' must be at least 2".into());
+    }
+    if settings.start_floor >= settings.n_floors {
+        return Err("'--start-floor' must be below the number of floors".into());
+    }
+    if settings.travel_time.is_zero() {
+        return Err("'--travel-ms' must be greater than zero".into());
+    }
+    Ok((port, settings))
+}
+
+fn run_command(simulator: &Simulator, line: &str) -> Result<(), String> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    match words.as_slice() {
+        [] => {}
+        ["press", button, floor] => simulator.press(parse_button(button)?, parse("floor", floor)?),
+        ["release", button, floor] => {
+            simulator.release(parse_button(button)?, parse("floor", floor)?)
+        }
+        ["obstruct", on] => simulator.set_obstruction(parse_switch(on)?),
+        ["stop", on] => simulator.set_stop_button(parse_switch(on)?),
+        ["power", on] => simulator.set_motor_power(parse_switch(on)?),
+        ["status"] => println!("{:?}", simulator.snapshot()),
+        _ => return Err(format!("unknown command '{line}', see --help")),
+    }
+    Ok(())
+}
+
+fn parse_button(button: &str) -> Result<Button, String> {
+    match button {
+        "up" => Ok(Button::Hall(Direction::Up)),
+        "down" => Ok(Button::Hall(Direction::Down)),
+        "cab" => Ok(Button::Cab),
+        _ => Err(format!(
+            "invalid button '{button}', must be one of: up, down, cab"
+        )),
+    }
+}
+
+fn parse_switch(value: &str) -> Result<bool, String> {
+    match value {
+        "on" => Ok(true),
+        "off" => Ok(false),
+        _ => Err(format!("invalid value '{value}', must be on or off")),
+    }
+}
+
+fn parse<T: FromStr>(key: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value '{value}' for {key}"))
+}
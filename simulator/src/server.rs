@@ -0,0 +1,176 @@
+use std::io::Result;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinSet;
+
+use interface::protocol::{Command, Response};
+use interface::types::Direction;
+
+use crate::car::Car;
+
+/// Accepts connections from controllers, letting all of them control the same car
+///
+/// The connections are closed when this future is dropped.
+pub async fn serve(listener: TcpListener, car: Arc<Mutex<Car>>) {
+    let mut connections = JoinSet::new();
+    loop {
+        let (stream, addr) = match listener.accept().await {
+            Ok(connection) => connection,
+            Err(e) => {
+                eprintln!("Failed to accept connection => error: {e}");
+                continue;
+            }
+        };
+        println!("Controller connected from {addr}");
+        while connections.try_join_next().is_some() {}
+
+        let car = car.clone();
+        connections.spawn(async move {
+            if let Err(e) = handle_connection(stream, car).await {
+                println!("Controller at {addr} disconnected ({e})");
+            }
+        });
+    }
+}
+
+/// Handles the commands of a controller until it disconnects
+///
+/// A command that can not be decoded closes the connection. It may have been a query,
+/// and a controller waiting for the reply would otherwise match the replies to the
+/// wrong queries from then on, so it has to reconnect instead.
+async fn handle_connection(mut stream: TcpStream, car: Arc<Mutex<Car>>) -> Result<()> {
+    loop {
+        let mut buffer = [0; 4];
+        stream.read_exact(&mut buffer).await?;
+
+        let command = Command::decode(buffer)?;
+
+        let reply = handle_command(&mut car.lock().unwrap(), command);
+        if let Some(reply) = reply {
+            stream.write_all(&reply.encode()).await?;
+        }
+    }
+}
+
+/// Applies a command to the car, and returns the reply if the command is a query
+fn handle_command(car: &mut Car, command: Command) -> Option<Response> {
+    let valid_floor = |floor: u8| usize::from(floor) < car.n_floors();
+
+    match command {
+        Command::ReloadConfig => {}
+        Command::MotorDirection(direction) => car.set_motor(match direction {
+            Some(Direction::Up) => 1,
+            Some(Direction::Down) => -1,
+            None => 0,
+        }),
+        Command::OrderButtonLight { button, floor, on } if valid_floor(floor) => {
+            car.set_order_light(button, usize::from(floor), on)
+        }
+        Command::FloorIndicator(floor) if valid_floor(floor) => {
+            car.set_floor_indicator(usize::from(floor))
+        }
+        Command::OrderButtonLight { .. } | Command::FloorIndicator(_) => {
+            eprintln!("Floor out of range in {command:?}")
+        }
+        Command::DoorOpenLight(on) => car.set_door_open_light(on),
+        Command::StopButtonLight(on) => car.set_stop_button_light(on),
+        Command::OrderButton { button, floor } => {
+            let pressed = valid_floor(floor) && car.is_pressed(button, usize::from(floor));
+            return Some(Response::OrderButton(pressed));
+        }
+        Command::Floor => return Some(Response::Floor(car.floor().map(|floor| floor as u8))),
+        Command::StopButton => return Some(Response::StopButton(car.stop_button())),
+        Command::Obstruction => return Some(Response::Obstruction(car.obstruction())),
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use tokio::time::timeout;
+
+    use interface::types::Button;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn invalid_commands_close_the_connection() {
+        let car = Arc::new(Mutex::new(Car::new(4, Duration::from_secs(2), 0)));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(serve(listener, car));
+
+        // An order button query for button 7
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream.write_all(&[6, 7, 0, 0]).await.unwrap();
+        let mut reply = Vec::new();
+        let read = timeout(Duration::from_secs(5), stream.read_to_end(&mut reply));
+        assert_eq!(read.await.unwrap().unwrap(), 0);
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream.write_all(&Command::Floor.encode()).await.unwrap();
+        let mut reply = [0; 4];
+        stream.read_exact(&mut reply).await.unwrap();
+        assert_eq!(Response::decode(reply).unwrap(), Response::Floor(Some(0)));
+        server.abort();
+    }
+
+    #[test]
+    fn commands_and_queries() {
+        let mut car = Car::new(4, Duration::from_secs(2), 0);
+
+        let (button, floor, on) = (Button::Cab, 3, true);
+        let light = Command::OrderButtonLight { button, floor, on };
+        assert_eq!(handle_command(&mut car, light), None);
+        assert!(car.is_lit(Button::Cab, 3));
+        handle_command(&mut car, Command::FloorIndicator(2));
+        assert_eq!(car.floor_indicator(), 2);
+        handle_command(&mut car, Command::DoorOpenLight(true));
+        assert!(car.door_open_light());
+        handle_command(&mut car, Command::MotorDirection(Some(Direction::Down)));
+        assert_eq!(car.motor(), -1);
+        handle_command(&mut car, Command::MotorDirection(None));
+        assert_eq!(car.motor(), 0);
+
+        let button = Button::Hall(Direction::Down);
+        car.set_pressed(button, 1, true);
+        let query = Command::OrderButton { button, floor: 1 };
+        assert_eq!(
+            handle_command(&mut car, query),
+            Some(Response::OrderButton(true))
+        );
+        let query = Command::OrderButton {
+            button: Button::Hall(Direction::Up),
+            floor: 1,
+        };
+        assert_eq!(
+            handle_command(&mut car, query),
+            Some(Response::OrderButton(false))
+        );
+        assert_eq!(
+            handle_command(&mut car, Command::Floor),
+            Some(Response::Floor(Some(0)))
+        );
+        car.set_obstruction(true);
+        let reply = handle_command(&mut car, Command::Obstruction);
+        assert_eq!(reply, Some(Response::Obstruction(true)));
+    }
+
+    #[test]
+    fn floors_out_of_range_are_ignored() {
+        let mut car = Car::new(4, Duration::from_secs(2), 0);
+
+        let (button, floor, on) = (Button::Cab, 4, true);
+        let light = Command::OrderButtonLight { button, floor, on };
+        assert_eq!(handle_command(&mut car, light), None);
+        assert_eq!(handle_command(&mut car, Command::FloorIndicator(9)), None);
+        assert_eq!(car.floor_indicator(), 0);
+        let query = Command::OrderButton { button, floor: 9 };
+        assert_eq!(
+            handle_command(&mut car, query),
+            Some(Response::OrderButton(false))
+        );
+    }
+}